@@ -1,21 +1,67 @@
+use crate::description_manipulator::Placement;
+use crate::github_pull_request::{TicketParser, TicketSource, TitleFormat};
+use crate::jira::{JiraCredentials, JiraTransitions};
+use crate::logger::LogFormat;
+use crate::secret::Secret;
+use crate::summary_template::SummaryTemplate;
+use crate::tracker::{parse_base_url, TrackerConfig, TrackerMapping};
+use log::LevelFilter;
 use octocrab::models::AppId;
+use std::collections::HashMap;
 use std::env;
-use url::{ParseError, Url};
+use std::fs;
+use std::str::FromStr;
+use url::Url;
 
 #[derive(Debug)]
-pub struct PemContents(pub String);
-#[derive(Debug)]
-pub struct LibRepoName(pub String);
-#[derive(Debug)]
+pub struct PemContents(pub Secret);
+/// Repository with shared code. The owner is optional, repositories without it are looked up
+/// under the owner of the repository the action runs in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibRepo {
+    pub owner: Option<String>,
+    pub name: String,
+}
+
+impl LibRepo {
+    /// Parse `owner/name` or just `name`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let (owner, name) = match input.split_once('/') {
+            Some((owner, name)) => (Some(owner.trim()), name.trim()),
+            None => (None, input),
+        };
+        let is_valid = |part: &str| !part.is_empty() && !part.contains('/');
+        if !is_valid(name) || !owner.is_none_or(is_valid) {
+            return Err(format!("Invalid lib repository: {}", input));
+        }
+        Ok(Self {
+            owner: owner.map(String::from),
+            name: String::from(name),
+        })
+    }
+
+    /// Owner of the repository, `default_owner` is used if it wasn't specified.
+    pub fn owner_or<'a>(&'a self, default_owner: &'a str) -> &'a str {
+        self.owner.as_deref().unwrap_or(default_owner)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
 pub struct JiraLink {
     host: Url,
 }
 
 impl JiraLink {
-    pub fn parse(input: &str) -> Result<Self, ParseError> {
-        Ok(Self {
-            host: Url::parse(input)?,
-        })
+    /// Parse the Jira host, an http(s) URL. Its path is kept, e.g. `https://acme.com/jira`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let host =
+            parse_base_url(input).ok_or_else(|| format!("{} is not an http(s) URL", input))?;
+        Ok(Self { host })
+    }
+
+    /// Base URL of the Jira REST API.
+    pub fn api_url(&self) -> Url {
+        self.host.join("rest/api/2/").unwrap()
     }
 
     pub fn ticket_url(&self, ticket_num: &str) -> String {
@@ -28,47 +74,622 @@ impl JiraLink {
     }
 }
 
-pub fn read_cli_args() -> Result<(AppId, PemContents, LibRepoName, Option<JiraLink>), String> {
-    let args: Vec<String> = env::args().collect();
+/// Where the action writes its summary of the PR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// The section of the PR description between the action markers.
+    Body,
+    /// A comment of the PR, found by the action markers. The description is left alone.
+    Comment,
+}
 
-    if args.len() != 5 {
-        return Err(String::from(
-            "Usage: program <app_id> <pem file contents> <lib_repo_name>",
-        ));
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "body" => Ok(Self::Body),
+            "comment" => Ok(Self::Comment),
+            other => Err(format!(
+                "Invalid output mode: {}, expected body or comment",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings the action runs with.
+#[derive(Debug)]
+pub struct Config {
+    pub app_id: AppId,
+    pub private_key: PemContents,
+    pub lib_repos: Vec<LibRepo>,
+    /// Jira of the tickets without a tracker of their own in `trackers`.
+    pub jira_link: Option<JiraLink>,
+    /// Trackers of the tickets by project key.
+    pub trackers: Vec<TrackerMapping>,
+    /// Show the summaries, statuses and assignees of the tickets, downloaded from Jira.
+    pub jira_credentials: Option<JiraCredentials>,
+    /// Fail the run if Jira doesn't know a ticket or it's closed.
+    pub fail_on_invalid_tickets: bool,
+    /// Move the Jira issues of the tickets through their workflow on `pull_request` events.
+    pub jira_transitions: JiraTransitions,
+    /// Link the PRs and their related lib PRs from the Jira issues of their tickets.
+    pub jira_remote_links: bool,
+    /// Number of pull requests requested per page when listing lib repo PRs.
+    pub lib_prs_page_size: u8,
+    /// Maximum number of pages downloaded per lib repo.
+    pub lib_prs_max_pages: u32,
+    /// Lib PRs closed or merged within this many days are listed next to the open ones.
+    /// `0` lists only open PRs.
+    pub closed_lib_prs_days: u32,
+    /// Update the related lib PRs with links back to the PRs that depend on them.
+    pub link_lib_prs: bool,
+    pub ticket_parser: TicketParser,
+    /// Report a missing ticket number with a failing check run.
+    pub require_ticket: bool,
+    pub output_mode: OutputMode,
+    /// Template of the section added to the PR description or comment.
+    pub template: SummaryTemplate,
+    pub placement: Placement,
+    /// Print the changes instead of writing them to GitHub.
+    pub dry_run: bool,
+    pub log_level: LevelFilter,
+    pub log_format: LogFormat,
+    /// Events sent by these users are skipped.
+    pub ignored_senders: Vec<String>,
+}
+
+/// A single setting of the action.
+///
+/// Every input can be passed either as a named command line flag, or through the `INPUT_*`
+/// environment variable that GitHub sets for each input declared in `action.yml`.
+/// The flag takes precedence over the environment variable.
+struct Input {
+    flag: &'static str,
+    env: &'static str,
+    description: &'static str,
+}
+
+const APP_ID: Input = Input {
+    flag: "--app-id",
+    env: "INPUT_APP_ID",
+    description: "App id of the KM Syncer app.",
+};
+const APP_PRIVATE_KEY: Input = Input {
+    flag: "--app-private-key",
+    env: "INPUT_APP_PRIVATE_KEY",
+    description: "Private key generated for the installed KM Syncer App.",
+};
+const LIB_REPO: Input = Input {
+    flag: "--lib-repo",
+    env: "INPUT_LIB_REPO_NAME",
+    description: "Repositories with common-lib, separated by commas or new lines. \
+Each one is either `name` (owned by the owner of the current repository) or `owner/name`.",
+};
+const JIRA_HOST: Input = Input {
+    flag: "--jira-host",
+    env: "INPUT_JIRA_HOST",
+    description: "Base url to JIRA board. Ticket numbers are not linked when omitted.",
+};
+const TRACKERS: Input = Input {
+    flag: "--trackers",
+    env: "INPUT_TRACKERS",
+    description: "Trackers of the tickets by project key, separated by commas or new lines, \
+e.g. `MD=jira:https://md.atlassian.net, ENG=linear:acme, OPS=youtrack:https://acme.youtrack.cloud, \
+#=github`. Other tickets link to --jira-host.",
+};
+const JIRA_EMAIL: Input = Input {
+    flag: "--jira-email",
+    env: "INPUT_JIRA_EMAIL",
+    description: "Email of the Jira user the ticket details are downloaded as. \
+Requires --jira-host and --jira-api-token.",
+};
+const JIRA_API_TOKEN: Input = Input {
+    flag: "--jira-api-token",
+    env: "INPUT_JIRA_API_TOKEN",
+    description: "API token of the Jira user. Tickets are shown with their summary, status and \
+assignee when it's set.",
+};
+const FAIL_ON_INVALID_TICKETS: Input = Input {
+    flag: "--fail-on-invalid-tickets",
+    env: "INPUT_FAIL_ON_INVALID_TICKETS",
+    description: "Fail the run when a ticket number is not found in Jira or its issue is closed \
+(true/false). Requires the Jira lookup. Default: false.",
+};
+const JIRA_TRANSITIONS: Input = Input {
+    flag: "--jira-transitions",
+    env: "INPUT_JIRA_TRANSITIONS",
+    description: "Jira transitions of the tickets on PR events, separated by commas or new lines, \
+e.g. `opened=In Review, merged=Done, OPS:merged=Deployed`. Requires the Jira lookup.",
+};
+const JIRA_REMOTE_LINKS: Input = Input {
+    flag: "--jira-remote-links",
+    env: "INPUT_JIRA_REMOTE_LINKS",
+    description: "Link the PRs and their related lib PRs from the Jira issues of their tickets \
+(true/false). Requires the Jira lookup. Default: false.",
+};
+
+const LIB_PRS_PAGE_SIZE: Input = Input {
+    flag: "--lib-prs-page-size",
+    env: "INPUT_LIB_PRS_PAGE_SIZE",
+    description: "Number of lib PRs downloaded per request (1-100). Default: 100.",
+};
+const LIB_PRS_MAX_PAGES: Input = Input {
+    flag: "--lib-prs-max-pages",
+    env: "INPUT_LIB_PRS_MAX_PAGES",
+    description: "Maximum number of pages of lib PRs downloaded per repository. Default: 10.",
+};
+
+const CLOSED_LIB_PRS_DAYS: Input = Input {
+    flag: "--closed-lib-prs-days",
+    env: "INPUT_CLOSED_LIB_PRS_DAYS",
+    description: "Include lib PRs closed or merged within this many days, 0 lists only open PRs. \
+Default: 0.",
+};
+
+const LINK_LIB_PRS: Input = Input {
+    flag: "--link-lib-prs",
+    env: "INPUT_LINK_LIB_PRS",
+    description: "Add links back to the dependent PRs to the related lib PRs (true/false). \
+The app needs write access to pull requests of the lib repositories. Default: false.",
+};
+
+const TICKET_SOURCES: Input = Input {
+    flag: "--ticket-sources",
+    env: "INPUT_TICKET_SOURCES",
+    description: "Where ticket numbers are looked for, in order: `title`, `branch` or both, \
+separated by commas. Tickets from the first source that has any are used. Default: title.",
+};
+
+const TITLE_FORMATS: Input = Input {
+    flag: "--title-formats",
+    env: "INPUT_TITLE_FORMATS",
+    description:
+        "Formats of the ticket numbers in the PR title, tried in order, separated by commas: \
+`brackets` ([BACK-12] msg), `colon` (BACK-12: msg), `parens` ((BACK-12) msg), \
+`conventional` (feat(BACK-12): msg). Default: brackets.",
+};
+
+const PROJECT_KEYS: Input = Input {
+    flag: "--project-keys",
+    env: "INPUT_PROJECT_KEYS",
+    description: "Jira project keys ticket numbers are accepted for, separated by commas, \
+e.g. BACK,MD. Other ticket-like strings (e.g. UTF-8) are ignored. Default: all keys are accepted.",
+};
+
+const REQUIRE_TICKET: Input = Input {
+    flag: "--require-ticket",
+    env: "INPUT_REQUIRE_TICKET",
+    description:
+        "Create a `km-action / ticket number` check run on the PR head commit, failing when \
+no ticket number is found (true/false). Needs the `checks: write` permission. Default: false.",
+};
+
+const OUTPUT_MODE: Input = Input {
+    flag: "--output-mode",
+    env: "INPUT_OUTPUT_MODE",
+    description: "Where the summary is written: `body` (the PR description) or `comment` \
+(a single comment of the action, deleted when there is nothing to say). Default: body.",
+};
+
+const TEMPLATE_FILE: Input = Input {
+    flag: "--template-file",
+    env: "INPUT_TEMPLATE_FILE",
+    description: "Path to a minijinja (Jinja2 like) template of the section the action adds to \
+the PR description or comment. Default: the built-in template.",
+};
+
+const PLACEMENT: Input = Input {
+    flag: "--placement",
+    env: "INPUT_PLACEMENT",
+    description: "Where the section is added to PR descriptions without it: `top`, `bottom` or \
+right after a Markdown heading, e.g. `## Related`. The section is never moved once it's added. \
+Default: bottom.",
+};
+
+const DRY_RUN: Input = Input {
+    flag: "--dry-run",
+    env: "INPUT_DRY_RUN",
+    description: "Print a diff of the PR bodies the action would set, without updating anything \
+(true/false). Default: false.",
+};
+
+const LOG_LEVEL: Input = Input {
+    flag: "--log-level",
+    env: "INPUT_LOG_LEVEL",
+    description: "Verbosity of the output: debug, info, warn or error. Default: info.",
+};
+
+const LOG_FORMAT: Input = Input {
+    flag: "--log-format",
+    env: "INPUT_LOG_FORMAT",
+    description: "Format of the output: `github` (workflow commands), `text` or `json`. \
+Default: github.",
+};
+
+const IGNORED_SENDERS: Input = Input {
+    flag: "--ignored-senders",
+    env: "INPUT_IGNORED_SENDERS",
+    description: "Logins whose events are skipped, separated by commas, e.g. the bot updating \
+the PR bodies. Default: github-actions[bot].",
+};
+
+const INPUTS: &[&Input] = &[
+    &APP_ID,
+    &APP_PRIVATE_KEY,
+    &LIB_REPO,
+    &JIRA_HOST,
+    &TRACKERS,
+    &JIRA_EMAIL,
+    &JIRA_API_TOKEN,
+    &FAIL_ON_INVALID_TICKETS,
+    &JIRA_TRANSITIONS,
+    &JIRA_REMOTE_LINKS,
+    &LIB_PRS_PAGE_SIZE,
+    &LIB_PRS_MAX_PAGES,
+    &CLOSED_LIB_PRS_DAYS,
+    &LINK_LIB_PRS,
+    &TICKET_SOURCES,
+    &TITLE_FORMATS,
+    &PROJECT_KEYS,
+    &REQUIRE_TICKET,
+    &OUTPUT_MODE,
+    &TEMPLATE_FILE,
+    &PLACEMENT,
+    &DRY_RUN,
+    &LOG_LEVEL,
+    &LOG_FORMAT,
+    &IGNORED_SENDERS,
+];
+
+/// Other names of the flags. `--lib-repo-name` matches the name of the action input.
+const FLAG_ALIASES: &[(&str, &Input)] = &[("--lib-repo-name", &LIB_REPO)];
+
+const DEFAULT_LIB_REPO: &str = "be-keto-mojo-common-lib";
+const DEFAULT_IGNORED_SENDERS: &str = "github-actions[bot]";
+const DEFAULT_LIB_PRS_PAGE_SIZE: u8 = 100;
+const DEFAULT_LIB_PRS_MAX_PAGES: u32 = 10;
+const DEFAULT_CLOSED_LIB_PRS_DAYS: u32 = 0;
+/// A hundred years, so the oldest date listed is always representable.
+const MAX_CLOSED_LIB_PRS_DAYS: u32 = 36500;
+
+/// Values of the inputs gathered from command line flags and the environment.
+struct Inputs {
+    values: HashMap<&'static str, String>,
+}
+
+impl Inputs {
+    /// Collect values of all known inputs.
+    /// * Flags can be passed as `--flag value` or `--flag=value`.
+    /// * A flag without a value (followed by another flag or by nothing) is read as `true`.
+    /// * Empty environment variables are treated as not set, because GitHub sets every declared
+    ///   input, including the optional ones that were not provided.
+    fn collect<A, E>(args: A, env_var: E) -> Result<Self, String>
+    where
+        A: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut values = HashMap::new();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(usage());
+            }
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), value.to_string()),
+                None => {
+                    let value = match args.peek() {
+                        Some(next) if !is_flag(next) => args.next().unwrap(),
+                        _ => String::from("true"),
+                    };
+                    (arg, value)
+                }
+            };
+            let input = find_input(&flag)
+                .ok_or_else(|| format!("Unknown argument: {}\n{}", flag, usage()))?;
+            values.insert(input.flag, value);
+        }
+
+        for input in INPUTS {
+            if values.contains_key(input.flag) {
+                continue;
+            }
+            if let Some(value) = env_var(input.env).filter(|v| !v.trim().is_empty()) {
+                values.insert(input.flag, value);
+            }
+        }
+
+        Ok(Self { values })
     }
 
-    let app_id = match args[1].parse::<u64>() {
-        Ok(n) => n,
-        Err(_) => {
-            return Err(format!("Invalid input: {}", args[1]));
+    fn get(&self, input: &Input) -> Option<&str> {
+        self.values.get(input.flag).map(|v| v.as_str())
+    }
+
+    /// Parse the value of an optional input, `default` is used when it is not set.
+    fn parsed<T: FromStr>(&self, input: &Input, default: T) -> Result<T, String> {
+        match self.get(input) {
+            None => Ok(default),
+            Some(value) => value
+                .trim()
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value of {}: {}", input.flag, value)),
         }
-    };
+    }
+
+    fn required(&self, input: &Input) -> Result<&str, String> {
+        self.get(input).ok_or_else(|| {
+            format!(
+                "Missing required input: pass {} or set {}.\n{}",
+                input.flag,
+                input.env,
+                usage()
+            )
+        })
+    }
+}
+
+/// Check whether `arg` is one of the known flags. Values are allowed to start with dashes
+/// (e.g. the PEM private key), so anything else is treated as a value.
+fn is_flag(arg: &str) -> bool {
+    let flag = arg.split_once('=').map_or(arg, |(flag, _)| flag);
+    flag == "--help" || find_input(flag).is_some()
+}
+
+/// Find the input of the `flag`, or of one of its aliases.
+fn find_input(flag: &str) -> Option<&'static Input> {
+    INPUTS
+        .iter()
+        .copied()
+        .find(|input| input.flag == flag)
+        .or_else(|| {
+            FLAG_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == flag)
+                .map(|(_, input)| *input)
+        })
+}
+
+fn usage() -> String {
+    let mut result = String::from("Usage: program [OPTIONS]\n\nOptions:");
+    for input in INPUTS {
+        let aliases: String = FLAG_ALIASES
+            .iter()
+            .filter(|(_, aliased)| aliased.flag == input.flag)
+            .map(|(alias, _)| format!(", {} <value>", alias))
+            .collect();
+        result.push_str(&format!(
+            "\n  {} <value>{} (env: {})\n      {}",
+            input.flag, aliases, input.env, input.description
+        ));
+    }
+    result
+}
 
-    let pem_contents = &args[2];
+impl Config {
+    fn parse<A, E>(args: A, env_var: E) -> Result<Self, String>
+    where
+        A: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let inputs = Inputs::collect(args, env_var)?;
 
-    let lib_name = &args[3];
+        let app_id = inputs.required(&APP_ID)?;
+        let app_id = match app_id.trim().parse::<u64>() {
+            Ok(n) => n,
+            Err(_) => {
+                return Err(format!("Invalid input: {}", app_id));
+            }
+        };
+
+        let pem_contents = inputs.required(&APP_PRIVATE_KEY)?;
+
+        let lib_repos = inputs
+            .get(&LIB_REPO)
+            .unwrap_or(DEFAULT_LIB_REPO)
+            .split([',', '\n'])
+            .filter(|v| !v.trim().is_empty())
+            .map(LibRepo::parse)
+            .collect::<Result<Vec<LibRepo>, String>>()?;
+
+        let jira_link = inputs
+            .get(&JIRA_HOST)
+            .and_then(|host| match JiraLink::parse(host) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    log::warn!("There was an error parsing JIRA host: {}", e);
+                    None
+                }
+            });
+        let trackers = match inputs.get(&TRACKERS) {
+            Some(trackers) => TrackerMapping::parse_list(trackers)?,
+            None => vec![],
+        };
+        let jira_credentials = match (inputs.get(&JIRA_EMAIL), inputs.get(&JIRA_API_TOKEN)) {
+            (None, None) => None,
+            (Some(email), Some(api_token)) => Some(JiraCredentials {
+                email: email.to_string(),
+                api_token: Secret::new(api_token.to_string()),
+            }),
+            _ => {
+                return Err(format!(
+                    "Both {} and {} are required for the Jira lookup",
+                    JIRA_EMAIL.flag, JIRA_API_TOKEN.flag
+                ))
+            }
+        };
+        let fail_on_invalid_tickets = inputs.parsed(&FAIL_ON_INVALID_TICKETS, false)?;
+        if fail_on_invalid_tickets && jira_credentials.is_none() {
+            return Err(format!(
+                "{} requires {} and {}",
+                FAIL_ON_INVALID_TICKETS.flag, JIRA_EMAIL.flag, JIRA_API_TOKEN.flag
+            ));
+        }
+        let jira_transitions = match inputs.get(&JIRA_TRANSITIONS) {
+            Some(jira_transitions) => JiraTransitions::parse(jira_transitions)?,
+            None => JiraTransitions::default(),
+        };
+        if !jira_transitions.is_empty() && jira_credentials.is_none() {
+            return Err(format!(
+                "{} requires {} and {}",
+                JIRA_TRANSITIONS.flag, JIRA_EMAIL.flag, JIRA_API_TOKEN.flag
+            ));
+        }
+        let jira_remote_links = inputs.parsed(&JIRA_REMOTE_LINKS, false)?;
+        if jira_remote_links && jira_credentials.is_none() {
+            return Err(format!(
+                "{} requires {} and {}",
+                JIRA_REMOTE_LINKS.flag, JIRA_EMAIL.flag, JIRA_API_TOKEN.flag
+            ));
+        }
+        let has_jira_tracker = trackers
+            .iter()
+            .any(|mapping| matches!(mapping.tracker, TrackerConfig::Jira(_)));
+        if jira_credentials.is_some() && inputs.get(&JIRA_HOST).is_none() && !has_jira_tracker {
+            return Err(format!(
+                "{} is required for the Jira lookup, unless {} has a Jira tracker",
+                JIRA_HOST.flag, TRACKERS.flag
+            ));
+        }
 
-    let jira_link = JiraLink::parse(&args[4]);
-    let jira_link = match jira_link {
-        Ok(v) => Some(v),
-        Err(e) => {
-            eprintln!("There was an error parsing JIRA host: {}", e);
-            None
+        let lib_prs_page_size = inputs.parsed(&LIB_PRS_PAGE_SIZE, DEFAULT_LIB_PRS_PAGE_SIZE)?;
+        if !(1..=100).contains(&lib_prs_page_size) {
+            return Err(format!(
+                "Invalid value of {}: {}, expected a number between 1 and 100",
+                LIB_PRS_PAGE_SIZE.flag, lib_prs_page_size
+            ));
         }
-    };
+        let lib_prs_max_pages = inputs.parsed(&LIB_PRS_MAX_PAGES, DEFAULT_LIB_PRS_MAX_PAGES)?;
+        if lib_prs_max_pages == 0 {
+            return Err(format!(
+                "Invalid value of {}: 0, at least one page has to be downloaded",
+                LIB_PRS_MAX_PAGES.flag
+            ));
+        }
+        let closed_lib_prs_days =
+            inputs.parsed(&CLOSED_LIB_PRS_DAYS, DEFAULT_CLOSED_LIB_PRS_DAYS)?;
+        if closed_lib_prs_days > MAX_CLOSED_LIB_PRS_DAYS {
+            return Err(format!(
+                "Invalid value of {}: {}, expected at most {} days",
+                CLOSED_LIB_PRS_DAYS.flag, closed_lib_prs_days, MAX_CLOSED_LIB_PRS_DAYS
+            ));
+        }
+        let link_lib_prs = inputs.parsed(&LINK_LIB_PRS, false)?;
+        let mut ticket_parser = TicketParser::default();
+        if let Some(sources) = inputs.get(&TICKET_SOURCES) {
+            ticket_parser.sources = sources
+                .split(',')
+                .filter(|v| !v.trim().is_empty())
+                .map(TicketSource::from_str)
+                .collect::<Result<Vec<TicketSource>, String>>()?;
+            if ticket_parser.sources.is_empty() {
+                return Err(format!(
+                    "Invalid value of {}: at least one source is required",
+                    TICKET_SOURCES.flag
+                ));
+            }
+        }
+        if let Some(formats) = inputs.get(&TITLE_FORMATS) {
+            ticket_parser.title_formats = formats
+                .split(',')
+                .filter(|v| !v.trim().is_empty())
+                .map(TitleFormat::from_str)
+                .collect::<Result<Vec<TitleFormat>, String>>()?;
+            if ticket_parser.title_formats.is_empty() {
+                return Err(format!(
+                    "Invalid value of {}: at least one format is required",
+                    TITLE_FORMATS.flag
+                ));
+            }
+        }
+        if let Some(project_keys) = inputs.get(&PROJECT_KEYS) {
+            ticket_parser.project_keys = project_keys
+                .split(',')
+                .map(|v| v.trim().to_uppercase())
+                .filter(|v| !v.is_empty())
+                .collect();
+        }
+        ticket_parser.issue_numbers = trackers
+            .iter()
+            .any(|mapping| matches!(mapping.tracker, TrackerConfig::GitHubIssues(_)));
+        let require_ticket = inputs.parsed(&REQUIRE_TICKET, false)?;
+        let output_mode = match inputs.get(&OUTPUT_MODE) {
+            Some(output_mode) => output_mode.parse()?,
+            None => OutputMode::Body,
+        };
+        let template = match inputs.get(&TEMPLATE_FILE) {
+            Some(path) => {
+                let source = fs::read_to_string(path)
+                    .map_err(|e| format!("Couldn't read the template file {}: {}", path, e))?;
+                SummaryTemplate::new(source, Some(path.to_string()))
+                    .map_err(|e| format!("Invalid template {}: {}", path, e))?
+            }
+            None => SummaryTemplate::default(),
+        };
+        let placement = match inputs.get(&PLACEMENT) {
+            Some(placement) => placement.parse()?,
+            None => Placement::Bottom,
+        };
+        let dry_run = inputs.parsed(&DRY_RUN, false)?;
+        let log_level = inputs.parsed(&LOG_LEVEL, LevelFilter::Info)?;
+        let log_format = match inputs.get(&LOG_FORMAT) {
+            Some(log_format) => log_format.parse()?,
+            None => LogFormat::Github,
+        };
+        let ignored_senders = inputs
+            .get(&IGNORED_SENDERS)
+            .unwrap_or(DEFAULT_IGNORED_SENDERS)
+            .split([',', '\n'])
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
 
-    Ok((
-        AppId(app_id),
-        PemContents(pem_contents.clone()),
-        LibRepoName(lib_name.clone()),
-        jira_link,
-    ))
+        Ok(Self {
+            app_id: AppId(app_id),
+            private_key: PemContents(Secret::new(pem_contents)),
+            lib_repos,
+            jira_link,
+            trackers,
+            jira_credentials,
+            fail_on_invalid_tickets,
+            jira_transitions,
+            jira_remote_links,
+            lib_prs_page_size,
+            lib_prs_max_pages,
+            closed_lib_prs_days,
+            link_lib_prs,
+            ticket_parser,
+            require_ticket,
+            output_mode,
+            template,
+            placement,
+            dry_run,
+            log_level,
+            log_format,
+            ignored_senders,
+        })
+    }
+}
+
+/// Read the action configuration from command line flags, falling back to `INPUT_*` environment
+/// variables set by GitHub for the action inputs.
+pub fn read_cli_args() -> Result<Config, String> {
+    Config::parse(env::args().skip(1), |name| env::var(name).ok())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cli::JiraLink;
+    use crate::cli::{Config, JiraLink, LibRepo, OutputMode};
+    use crate::description_manipulator::Placement;
+    use crate::github_pull_request::{TicketSource, TitleFormat};
+    use crate::jira::LifecycleEvent;
+    use crate::logger::LogFormat;
+    use crate::tracker::{TrackerConfig, TrackerMapping};
+    use log::LevelFilter;
     use rstest::rstest;
+    use std::collections::{HashMap, HashSet};
 
     #[rstest]
     #[case("BACK-1234", "https://test.com/browse/BACK-1234")]
@@ -80,4 +701,311 @@ mod tests {
 
         assert_eq!(result, expected_result);
     }
+
+    #[rstest]
+    #[case(
+        "https://acme.com/jira",
+        "https://acme.com/jira/browse/BACK-1",
+        "https://acme.com/jira/rest/api/2/"
+    )]
+    #[case(
+        "https://acme.com/jira/",
+        "https://acme.com/jira/browse/BACK-1",
+        "https://acme.com/jira/rest/api/2/"
+    )]
+    #[case(
+        "http://jira.local",
+        "http://jira.local/browse/BACK-1",
+        "http://jira.local/rest/api/2/"
+    )]
+    fn test_jira_link_with_path(
+        #[case] host: &str,
+        #[case] expected_ticket_url: &str,
+        #[case] expected_api_url: &str,
+    ) {
+        let jira_link = JiraLink::parse(host).unwrap();
+
+        assert_eq!(jira_link.ticket_url("BACK-1"), expected_ticket_url);
+        assert_eq!(jira_link.api_url().as_str(), expected_api_url);
+    }
+
+    #[rstest]
+    #[case("ftp://acme.com/jira")]
+    #[case("mailto:jira@acme.com")]
+    #[case("not a url")]
+    fn test_jira_link_invalid(#[case] host: &str) {
+        assert!(JiraLink::parse(host).is_err());
+    }
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::parse(args.iter().map(|a| a.to_string()), |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[rstest]
+    #[case(&["--app-id", "12", "--app-private-key", "key"], &[], "key")]
+    #[case(&["--app-id=12", "--app-private-key=key"], &[], "key")]
+    #[case(&[], &[("INPUT_APP_ID", "12"), ("INPUT_APP_PRIVATE_KEY", "key")], "key")]
+    #[case(&["--app-id", "12"], &[("INPUT_APP_ID", "99"), ("INPUT_APP_PRIVATE_KEY", "key")], "key")]
+    #[case(&["--app-private-key", "-----BEGIN-----", "--app-id", "12"], &[], "-----BEGIN-----")]
+    #[case(&["--app-private-key", "-----BEGIN-----"], &[("INPUT_APP_ID", "12"), ("INPUT_APP_PRIVATE_KEY", "key")], "-----BEGIN-----")]
+    fn test_parse_config_sources(
+        #[case] args: &[&str],
+        #[case] env: &[(&str, &str)],
+        #[case] expected_private_key: &str,
+    ) {
+        let config = parse(args, env).unwrap();
+
+        assert_eq!(config.app_id.0, 12);
+        assert_eq!(config.private_key.0.expose(), expected_private_key);
+        assert_eq!(
+            config.lib_repos,
+            vec![LibRepo::parse("be-keto-mojo-common-lib").unwrap()]
+        );
+        assert!(config.jira_link.is_none());
+        assert!(config.trackers.is_empty());
+        assert!(!config.ticket_parser.issue_numbers);
+        assert!(config.jira_credentials.is_none());
+        assert!(!config.fail_on_invalid_tickets);
+        assert!(config.jira_transitions.is_empty());
+        assert!(!config.jira_remote_links);
+        assert_eq!(config.lib_prs_page_size, 100);
+        assert_eq!(config.lib_prs_max_pages, 10);
+        assert_eq!(config.closed_lib_prs_days, 0);
+        assert!(!config.link_lib_prs);
+        assert_eq!(config.ticket_parser.sources, vec![TicketSource::Title]);
+        assert_eq!(
+            config.ticket_parser.title_formats,
+            vec![TitleFormat::Brackets]
+        );
+        assert!(config.ticket_parser.project_keys.is_empty());
+        assert!(!config.require_ticket);
+        assert_eq!(config.output_mode, OutputMode::Body);
+        assert_eq!(format!("{:?}", config.template), "SummaryTemplate(default)");
+        assert_eq!(config.placement, Placement::Bottom);
+        assert!(!config.dry_run);
+        assert_eq!(config.log_level, LevelFilter::Info);
+        assert_eq!(config.log_format, LogFormat::Github);
+        assert_eq!(config.ignored_senders, vec!["github-actions[bot]"]);
+    }
+
+    #[test]
+    fn test_parse_config_optional_inputs() {
+        let config = parse(
+            &[
+                "--lib-repo",
+                "my-lib",
+                "--link-lib-prs",
+                "--dry-run",
+                "--jira-host",
+                "https://test.com",
+                "--jira-email",
+                "bot@example.com",
+            ],
+            &[
+                ("INPUT_APP_ID", "12"),
+                ("INPUT_APP_PRIVATE_KEY", "key"),
+                ("INPUT_LIB_REPO_NAME", "other-lib"),
+                ("INPUT_LIB_PRS_PAGE_SIZE", "50"),
+                ("INPUT_JIRA_API_TOKEN", "token"),
+                ("INPUT_TRACKERS", "ENG=linear:acme\n#=github\n"),
+                ("INPUT_FAIL_ON_INVALID_TICKETS", "true"),
+                ("INPUT_JIRA_TRANSITIONS", "opened=In Review\nmerged=Done\n"),
+                ("INPUT_JIRA_REMOTE_LINKS", "true"),
+                ("INPUT_TICKET_SOURCES", "branch"),
+                ("INPUT_TITLE_FORMATS", "conventional, colon"),
+                ("INPUT_PROJECT_KEYS", "BACK, md,"),
+                ("INPUT_REQUIRE_TICKET", "true"),
+                ("INPUT_OUTPUT_MODE", "comment"),
+                ("INPUT_TEMPLATE_FILE", "src/default_template.md.jinja"),
+                ("INPUT_PLACEMENT", "## Related"),
+                ("INPUT_LOG_LEVEL", "debug"),
+                ("INPUT_LOG_FORMAT", "json"),
+                ("INPUT_IGNORED_SENDERS", "km-bot[bot], github-actions[bot]"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.lib_repos, vec![LibRepo::parse("my-lib").unwrap()]);
+        assert_eq!(config.lib_prs_page_size, 50);
+        assert!(config.link_lib_prs);
+        assert_eq!(config.ticket_parser.sources, vec![TicketSource::Branch]);
+        assert!(config.require_ticket);
+        assert_eq!(config.output_mode, OutputMode::Comment);
+        assert_eq!(
+            format!("{:?}", config.template),
+            "SummaryTemplate(src/default_template.md.jinja)"
+        );
+        assert_eq!(
+            config.placement,
+            Placement::AfterHeading(String::from("## Related"))
+        );
+        assert!(config.dry_run);
+        assert_eq!(config.log_level, LevelFilter::Debug);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(
+            config.ignored_senders,
+            vec!["km-bot[bot]", "github-actions[bot]"]
+        );
+        assert_eq!(
+            config.ticket_parser.title_formats,
+            vec![TitleFormat::Conventional, TitleFormat::Colon]
+        );
+        assert_eq!(
+            config.ticket_parser.project_keys,
+            HashSet::from([String::from("BACK"), String::from("MD")])
+        );
+        let jira_credentials = config.jira_credentials.unwrap();
+        assert_eq!(jira_credentials.email, "bot@example.com");
+        assert_eq!(jira_credentials.api_token.expose(), "token");
+        assert!(config.fail_on_invalid_tickets);
+        assert_eq!(
+            config
+                .jira_transitions
+                .transition("BACK-1", LifecycleEvent::Merged),
+            Some("Done")
+        );
+        assert!(config.jira_remote_links);
+        assert_eq!(
+            config.trackers,
+            vec![
+                TrackerMapping {
+                    project_key: String::from("ENG"),
+                    tracker: TrackerConfig::Linear(String::from("acme")),
+                },
+                TrackerMapping {
+                    project_key: String::from("#"),
+                    tracker: TrackerConfig::GitHubIssues(None),
+                },
+            ]
+        );
+        assert!(config.ticket_parser.issue_numbers);
+        assert_eq!(
+            config.jira_link.unwrap().ticket_url("BACK-1"),
+            "https://test.com/browse/BACK-1"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_multiple_lib_repos() {
+        let config = parse(
+            &["--app-id", "12", "--app-private-key", "key"],
+            &[("INPUT_LIB_REPO_NAME", "lib-a, other-org/lib-b\nlib-c\n")],
+        )
+        .unwrap();
+
+        let repos: Vec<(Option<&str>, &str)> = config
+            .lib_repos
+            .iter()
+            .map(|r| (r.owner.as_deref(), r.name.as_str()))
+            .collect();
+        assert_eq!(
+            repos,
+            vec![
+                (None, "lib-a"),
+                (Some("other-org"), "lib-b"),
+                (None, "lib-c")
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(&["--lib-repo", "my-lib"])]
+    #[case(&["--lib-repo=my-lib"])]
+    #[case(&["--lib-repo-name", "my-lib"])]
+    #[case(&["--lib-repo-name=my-lib"])]
+    fn test_parse_config_lib_repo_flags(#[case] lib_repo_args: &[&str]) {
+        let mut args = vec!["--app-id", "12", "--app-private-key", "key"];
+        args.extend_from_slice(lib_repo_args);
+
+        let config = parse(&args, &[("INPUT_LIB_REPO_NAME", "other-lib")]).unwrap();
+
+        assert_eq!(config.lib_repos, vec![LibRepo::parse("my-lib").unwrap()]);
+    }
+
+    #[test]
+    fn test_parse_config_jira_tracker_without_jira_host() {
+        let config = parse(
+            &["--app-id", "12", "--app-private-key", "key"],
+            &[
+                ("INPUT_JIRA_EMAIL", "bot@example.com"),
+                ("INPUT_JIRA_API_TOKEN", "token"),
+                ("INPUT_TRACKERS", "BACK=jira:https://back.atlassian.net"),
+            ],
+        )
+        .unwrap();
+
+        assert!(config.jira_link.is_none());
+        assert!(config.jira_credentials.is_some());
+        assert_eq!(config.trackers[0].project_key, "BACK");
+        assert!(!config.ticket_parser.issue_numbers);
+    }
+
+    #[rstest]
+    #[case("lib", Ok((None, "lib")))]
+    #[case("org/lib", Ok((Some("org"), "lib")))]
+    #[case("org/", Err(()))]
+    #[case("/lib", Err(()))]
+    #[case("org/lib/extra", Err(()))]
+    fn test_parse_lib_repo(
+        #[case] input: &str,
+        #[case] expected: Result<(Option<&str>, &str), ()>,
+    ) {
+        let result = LibRepo::parse(input);
+        match expected {
+            Ok((owner, name)) => {
+                let repo = result.unwrap();
+                assert_eq!(repo.owner.as_deref(), owner);
+                assert_eq!(repo.name, name);
+                assert_eq!(repo.owner_or("default"), owner.unwrap_or("default"));
+            }
+            Err(_) => assert!(result.is_err()),
+        }
+    }
+
+    #[rstest]
+    #[case(&["--app-private-key", "key"], &[], "--app-id")]
+    #[case(&["--app-id", "12"], &[("INPUT_APP_PRIVATE_KEY", "")], "--app-private-key")]
+    #[case(&["--app-id", "abc", "--app-private-key", "key"], &[], "Invalid input: abc")]
+    #[case(&["--app-id", "12", "--unknown", "x"], &[], "Unknown argument: --unknown")]
+    #[case(&["--help"], &[], "Usage:")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--lib-prs-page-size", "101"], &[], "--lib-prs-page-size: 101")]
+    #[case(&["--app-id", "1", "--app-private-key", "k"], &[("INPUT_LIB_PRS_PAGE_SIZE", "many")], "--lib-prs-page-size: many")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--lib-prs-max-pages", "0"], &[], "--lib-prs-max-pages: 0")]
+    #[case(&["--app-id", "1", "--app-private-key", "k"], &[("INPUT_CLOSED_LIB_PRS_DAYS", "4294967295")], "--closed-lib-prs-days: 4294967295, expected at most 36500 days")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--link-lib-prs", "yes"], &[], "--link-lib-prs: yes")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--ticket-sources", "title,body"], &[], "Invalid ticket source: body")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--ticket-sources", ","], &[], "--ticket-sources: at least one")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--title-formats", "brackets,dashes"], &[], "Invalid title format: dashes")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--require-ticket", "maybe"], &[], "--require-ticket: maybe")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--output-mode", "issue"], &[], "Invalid output mode: issue")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--template-file", "missing.jinja"], &[], "Couldn't read the template file missing.jinja")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--template-file", "src/test_resources/invalid_template.md.jinja"], &[], "Invalid template src/test_resources/invalid_template.md.jinja: syntax error")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--placement", "middle"], &[], "Invalid placement: middle")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-host", "https://test.com", "--jira-email", "bot@example.com"], &[], "Both --jira-email and --jira-api-token are required")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-email", "bot@example.com"], &[("INPUT_JIRA_API_TOKEN", "token")], "--jira-host is required")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-email", "bot@example.com", "--trackers", "ENG=linear:acme"], &[("INPUT_JIRA_API_TOKEN", "token")], "--jira-host is required")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--trackers", "ENG=asana:acme"], &[], "Invalid tracker: ENG=asana:acme")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--fail-on-invalid-tickets"], &[], "--fail-on-invalid-tickets requires --jira-email and --jira-api-token")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--fail-on-invalid-tickets", "sure"], &[], "--fail-on-invalid-tickets: sure")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-transitions", "merged=Done"], &[], "--jira-transitions requires --jira-email and --jira-api-token")]
+    #[case(&["--app-id", "1", "--app-private-key", "k"], &[("INPUT_JIRA_TRANSITIONS", "pushed=Done")], "Invalid PR event: pushed")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-remote-links"], &[], "--jira-remote-links requires --jira-email and --jira-api-token")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--jira-remote-links", "yes"], &[], "--jira-remote-links: yes")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--log-level", "loud"], &[], "--log-level: loud")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--log-format", "yaml"], &[], "Invalid log format: yaml")]
+    #[case(&["--app-id", "1", "--app-private-key", "k", "--title-formats", " "], &[], "--title-formats: at least one")]
+    fn test_parse_config_errors(
+        #[case] args: &[&str],
+        #[case] env: &[(&str, &str)],
+        #[case] expected_message: &str,
+    ) {
+        let result = parse(args, env);
+        assert!(result.unwrap_err().contains(expected_message));
+    }
 }
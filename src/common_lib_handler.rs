@@ -1,44 +1,92 @@
+use chrono::{DateTime, Utc};
 use jsonwebtoken::EncodingKey;
-use octocrab;
-use octocrab::models::{AppId, InstallationToken};
+use octocrab::models::{AppId, Installation, InstallationToken};
 use octocrab::params::apps::CreateInstallationAccessToken;
-use octocrab::{Octocrab, OctocrabBuilder};
+use octocrab::params::pulls::Sort;
+use octocrab::params::{Direction, State};
+use octocrab::Page;
+use octocrab::{FromResponse, Octocrab, OctocrabBuilder};
+use reqwest::StatusCode;
+use std::fmt;
+
+use crate::error::describe_github_error;
+use crate::github_pull_request::PullRequest;
+use crate::secret::Secret;
 
 #[derive(Debug)]
-pub struct GithubSetupError {
-    pub jsonwebtoken_error: Option<jsonwebtoken::errors::Error>,
-    pub github_error: Option<octocrab::Error>,
+pub enum GithubSetupError {
+    JsonWebToken(jsonwebtoken::errors::Error),
+    Github(octocrab::Error),
+    /// The app is not installed on the account owning the repository, or the installation
+    /// wasn't granted access to it.
+    NoInstallation {
+        owner: String,
+        repo: String,
+    },
+    /// The repositories belong to different installations of the app, one access token can't
+    /// cover them all.
+    DifferentInstallations {
+        owner: String,
+        first_repo: String,
+        repo: String,
+    },
+}
+
+impl fmt::Display for GithubSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::JsonWebToken(e) => write!(f, "invalid app private key: {}", e),
+            Self::Github(e) => write!(f, "GitHub API error: {}", describe_github_error(e)),
+            Self::NoInstallation { owner, repo } => write!(
+                f,
+                "the app has no installation with access to {}/{}",
+                owner, repo
+            ),
+            Self::DifferentInstallations {
+                owner,
+                first_repo,
+                repo,
+            } => write!(
+                f,
+                "{}/{} and {}/{} belong to different installations of the app",
+                owner, first_repo, owner, repo
+            ),
+        }
+    }
 }
 
 impl From<octocrab::Error> for GithubSetupError {
     fn from(e: octocrab::Error) -> Self {
-        Self {
-            github_error: Some(e),
-            jsonwebtoken_error: None,
-        }
+        Self::Github(e)
     }
 }
 
 impl From<jsonwebtoken::errors::Error> for GithubSetupError {
     fn from(e: jsonwebtoken::errors::Error) -> Self {
-        Self {
-            github_error: None,
-            jsonwebtoken_error: Some(e),
-        }
+        Self::JsonWebToken(e)
     }
 }
 
-/// Get Octocrab instance to query the lib repository.
+/// Get Octocrab instance to query the lib repositories.
 ///
 /// Steps:
 /// * Authenticate with App key and app private key
-/// * Retrieve installations
-/// * Create access token for the lib repository.
-/// * Return octocrab instance authenticated with access_token for the lib repository.
+/// * Find the installation that has access to the lib repositories
+/// * Create a single access token for all the lib repositories.
+/// * Return octocrab instance authenticated with access_token for the lib repositories.
 ///
-/// # Panics
+/// An installation token can only cover repositories of one installation, so all
+/// `lib_repo_names` have to belong to the same `owner`.
 ///
-/// The function will panic if provided private key fails to authenticate.
+/// `None` is returned when there are no `lib_repo_names`.
+///
+/// # Errors
+///
+/// * `GithubSetupError::JsonWebToken` if the private key is invalid.
+/// * `GithubSetupError::NoInstallation` if the app has no access to one of the repositories.
+/// * `GithubSetupError::DifferentInstallations` if the repositories belong to different
+///   installations of the app.
+/// * `GithubSetupError::Github` if a GitHub API request fails.
 ///
 /// # Useful resources
 ///
@@ -47,52 +95,546 @@ impl From<jsonwebtoken::errors::Error> for GithubSetupError {
 pub async fn get_octocrab_instance_for_lib_repo(
     app_id: AppId,
     private_key: &[u8],
-    lib_repo_name: &str,
-) -> Result<Octocrab, GithubSetupError> {
+    owner: &str,
+    lib_repo_names: &[String],
+) -> Result<Option<Octocrab>, GithubSetupError> {
     let key = EncodingKey::from_rsa_pem(private_key)?;
 
     let token = octocrab::auth::create_jwt(app_id, &key)?;
 
     let crab = OctocrabBuilder::new().personal_token(token).build()?;
 
-    Ok(get_client_for_repo_from_installations(&crab, lib_repo_name).await?)
+    get_client_for_repo_from_installations(&crab, owner, lib_repo_names).await
 }
 
-/// This function queries app installations and attempts to retrieve an access token for the requested
-/// `repo_name`.
+/// This function looks up the app installation for the requested repositories and attempts to
+/// retrieve one access token for all the requested `repo_names`.
+///
+/// The installation is found with the repository installation endpoint, so the right one is used
+/// even when the app is installed on multiple accounts.
 ///
 /// # Returns
 ///
-/// An octocrab client with authentication for the requested repo.
+/// An octocrab client with authentication for the requested repos, `None` if there are none.
 pub async fn get_client_for_repo_from_installations(
     octocrab_: &Octocrab,
-    repo_name: &str,
-) -> Result<Octocrab, GithubSetupError> {
-    let installations = octocrab_.apps().installations().send().await?.take_items();
+    owner: &str,
+    repo_names: &[String],
+) -> Result<Option<Octocrab>, GithubSetupError> {
+    let mut installation: Option<Installation> = None;
+    for repo_name in repo_names {
+        let repo_installation = get_repository_installation(octocrab_, owner, repo_name)
+            .await?
+            .ok_or_else(|| GithubSetupError::NoInstallation {
+                owner: owner.to_string(),
+                repo: repo_name.clone(),
+            })?;
+        match &installation {
+            Some(found) if found.id != repo_installation.id => {
+                return Err(GithubSetupError::DifferentInstallations {
+                    owner: owner.to_string(),
+                    first_repo: repo_names[0].clone(),
+                    repo: repo_name.clone(),
+                })
+            }
+            Some(_) => {}
+            None => installation = Some(repo_installation),
+        }
+    }
+    let Some(installation) = installation else {
+        return Ok(None);
+    };
+
     let mut create_access_token = CreateInstallationAccessToken::default();
-    create_access_token.repositories = vec![String::from(repo_name)];
+    create_access_token.repositories = repo_names.to_vec();
 
+    let access_tokens_url = match installation.access_tokens_url {
+        Some(url) => url,
+        None => format!("app/installations/{}/access_tokens", installation.id),
+    };
     let access_to_repo: InstallationToken = octocrab_
-        .post(
-            installations[0].access_tokens_url.as_ref().unwrap(),
-            Some(&create_access_token),
-        )
+        .post(access_tokens_url, Some(&create_access_token))
         .await?;
+    let token = Secret::new(access_to_repo.token);
+    token.add_mask();
     let octocrab_for_repo = OctocrabBuilder::new()
-        .personal_token(access_to_repo.token)
+        .personal_token(token.expose().to_string())
         .build()?;
 
-    Ok(octocrab_for_repo)
+    Ok(Some(octocrab_for_repo))
+}
+
+/// Download pull requests of the repository, following the pagination links.
+///
+/// All open pull requests are downloaded. When `closed_since` is set, pull requests closed (or
+/// merged) after that moment are included too.
+///
+/// At most `max_pages` pages of `page_size` pull requests are downloaded per state. A warning is
+/// printed when the limit is reached before the last page.
+pub async fn get_repo_pull_requests(
+    octocrab_: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    page_size: u8,
+    max_pages: u32,
+    closed_since: Option<DateTime<Utc>>,
+) -> octocrab::Result<Vec<PullRequest>> {
+    let mut pull_requests = list_pull_requests(
+        octocrab_,
+        owner,
+        repo_name,
+        State::Open,
+        page_size,
+        max_pages,
+        None,
+    )
+    .await?;
+
+    if let Some(closed_since) = closed_since {
+        let closed = list_pull_requests(
+            octocrab_,
+            owner,
+            repo_name,
+            State::Closed,
+            page_size,
+            max_pages,
+            Some(closed_since),
+        )
+        .await?;
+        pull_requests.extend(closed.into_iter().filter(|pr| {
+            pr.closed_at
+                .is_some_and(|closed_at| closed_at >= closed_since)
+        }));
+    }
+
+    Ok(pull_requests.into_iter().map(|v| v.into()).collect())
+}
+
+/// List pull requests in the given `state`, most recently updated first.
+///
+/// Pages are downloaded until the last one, until `max_pages` is reached, or until a page
+/// contains a pull request last updated before `updated_since`.
+async fn list_pull_requests(
+    octocrab_: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    state: State,
+    page_size: u8,
+    max_pages: u32,
+    updated_since: Option<DateTime<Utc>>,
+) -> octocrab::Result<Vec<octocrab::models::pulls::PullRequest>> {
+    let is_outdated = |page: &Page<octocrab::models::pulls::PullRequest>| match updated_since {
+        Some(since) => page
+            .items
+            .iter()
+            .any(|pr| pr.updated_at.is_some_and(|updated_at| updated_at < since)),
+        None => false,
+    };
+
+    let mut page = octocrab_
+        .pulls(owner, repo_name)
+        .list()
+        .state(state)
+        .sort(Sort::Updated)
+        .direction(Direction::Descending)
+        .per_page(page_size)
+        .send()
+        .await?;
+    let mut pull_requests = vec![];
+    let mut pages_downloaded = 1;
+
+    loop {
+        let last_needed_page = is_outdated(&page);
+        pull_requests.append(&mut page.take_items());
+        if last_needed_page || page.next.is_none() {
+            break;
+        }
+        if pages_downloaded >= max_pages {
+            log::warn!(
+                "Stopped listing pull requests of {}/{} after {} pages.",
+                owner,
+                repo_name,
+                pages_downloaded
+            );
+            break;
+        }
+        page = match octocrab_.get_page(&page.next).await? {
+            Some(next_page) => next_page,
+            None => break,
+        };
+        pages_downloaded += 1;
+    }
+
+    Ok(pull_requests)
+}
+
+/// Installation of the app with access to the repository. GitHub responds with 404 when the app
+/// is not installed for it, `None` is returned then.
+async fn get_repository_installation(
+    octocrab_: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+) -> octocrab::Result<Option<Installation>> {
+    let url = octocrab_.absolute_url(format!("repos/{}/{}/installation", owner, repo_name))?;
+    let response = octocrab_._get(url, None::<&()>).await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = octocrab::map_github_error(response).await?;
+    Installation::from_response(response).await.map(Some)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common_lib_handler::get_octocrab_instance_for_lib_repo;
+    use crate::common_lib_handler::{
+        get_client_for_repo_from_installations, get_octocrab_instance_for_lib_repo,
+        get_repo_pull_requests, GithubSetupError,
+    };
+    use crate::test_utils::mock_octocrab;
+    use chrono::{Duration, Utc};
     use octocrab::models::AppId;
+    use octocrab::Octocrab;
+    use rstest::rstest;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use std::fs::File;
     use std::io::Read;
 
+    fn pull_request_json(number: u64) -> serde_json::Value {
+        json!({
+            "url": format!("https://api.github.com/repos/org/lib/pulls/{}", number),
+            "id": number,
+            "number": number,
+            "title": format!("[BACK-{}] Lib PR", number),
+            "locked": false,
+            "maintainer_can_modify": false,
+            "head": {"ref": "feature", "sha": "abc"},
+            "base": {"ref": "main", "sha": "def"},
+        })
+    }
+
+    /// Mock `pages` pages of the lib repo pull requests list, two pull requests per page.
+    async fn mock_pull_request_pages(pages: u64) -> (MockServer, Octocrab) {
+        let server = MockServer::start().await;
+        for page in 1..=pages {
+            let mut response = ResponseTemplate::new(200).set_body_json(json!([
+                pull_request_json(page * 2 - 1),
+                pull_request_json(page * 2)
+            ]));
+            if page < pages {
+                response = response.insert_header(
+                    "Link",
+                    format!(
+                        "<{}/repos/org/lib/pulls?per_page=2&page={}>; rel=\"next\"",
+                        server.uri(),
+                        page + 1
+                    )
+                    .as_str(),
+                );
+            }
+            let mock = Mock::given(method("GET")).and(path("/repos/org/lib/pulls"));
+            // Requests for the following pages match the first page mock too, they take priority.
+            let mock = match page {
+                1 => mock
+                    .and(query_param("per_page", "2"))
+                    .respond_with(response),
+                _ => mock
+                    .and(query_param("page", page.to_string()))
+                    .respond_with(response)
+                    .with_priority(1),
+            };
+            mock.mount(&server).await;
+        }
+        let octo = mock_octocrab(&server);
+        (server, octo)
+    }
+
+    #[rstest]
+    #[case(3, 10, 6)]
+    #[case(3, 2, 4)]
+    #[case(1, 1, 2)]
+    #[tokio::test]
+    async fn test_get_repo_pull_requests_pagination(
+        #[case] pages: u64,
+        #[case] max_pages: u32,
+        #[case] expected_count: usize,
+    ) {
+        let (_server, octo) = mock_pull_request_pages(pages).await;
+
+        let result = get_repo_pull_requests(&octo, "org", "lib", 2, max_pages, None)
+            .await
+            .unwrap();
+
+        let numbers: Vec<u64> = result.iter().map(|pr| pr.number).collect();
+        assert_eq!(numbers, (1..=expected_count as u64).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn test_get_repo_pull_requests_recently_closed() {
+        let server = MockServer::start().await;
+        let days_ago = |days: i64| (Utc::now() - Duration::days(days)).to_rfc3339();
+        let closed_pull_request = |number: u64, days: i64| {
+            let mut pr = pull_request_json(number);
+            pr["state"] = json!("closed");
+            pr["updated_at"] = json!(days_ago(days));
+            pr["closed_at"] = json!(days_ago(days));
+            pr
+        };
+        Mock::given(method("GET"))
+            .and(path("/repos/org/lib/pulls"))
+            .and(query_param("state", "open"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([pull_request_json(1)])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/org/lib/pulls"))
+            .and(query_param("state", "closed"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([
+                        closed_pull_request(2, 1),
+                        closed_pull_request(3, 100)
+                    ]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            "<{}/repos/org/lib/pulls?page=2>; rel=\"next\"",
+                            server.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(&server)
+            .await;
+        // Older pull requests are on the following pages, those should not be downloaded.
+        Mock::given(method("GET"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let octo = mock_octocrab(&server);
+
+        let result = get_repo_pull_requests(
+            &octo,
+            "org",
+            "lib",
+            2,
+            10,
+            Some(Utc::now() - Duration::days(30)),
+        )
+        .await
+        .unwrap();
+
+        let numbers: Vec<u64> = result.iter().map(|pr| pr.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+    }
+
+    fn installation_json(id: u64, access_tokens_url: Option<String>) -> serde_json::Value {
+        let url = "https://api.github.com/users/org";
+        json!({
+            "id": id,
+            "account": {
+                "login": "org",
+                "id": 1,
+                "node_id": "MDQ6VXNlcjE=",
+                "avatar_url": "https://github.com/images/error/org.gif",
+                "gravatar_id": "",
+                "url": url,
+                "html_url": "https://github.com/org",
+                "followers_url": format!("{}/followers", url),
+                "following_url": format!("{}/following", url),
+                "gists_url": format!("{}/gists", url),
+                "starred_url": format!("{}/starred", url),
+                "subscriptions_url": format!("{}/subscriptions", url),
+                "organizations_url": format!("{}/orgs", url),
+                "repos_url": format!("{}/repos", url),
+                "events_url": format!("{}/events", url),
+                "received_events_url": format!("{}/received_events", url),
+                "type": "Organization",
+                "site_admin": false,
+            },
+            "access_tokens_url": access_tokens_url,
+            "permissions": {"pull_requests": "read"},
+            "events": [],
+        })
+    }
+
+    async fn mock_installation(server: &MockServer, repo_name: &str, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/org/{}/installation", repo_name)))
+            .respond_with(response)
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    #[tokio::test]
+    async fn test_get_client_for_repo_from_installations(#[case] with_access_tokens_url: bool) {
+        let server = MockServer::start().await;
+        let access_tokens_url = with_access_tokens_url
+            .then(|| format!("{}/app/installations/7/access_tokens", server.uri()));
+        for repo_name in ["lib-a", "lib-b"] {
+            let installation = installation_json(7, access_tokens_url.clone());
+            mock_installation(
+                &server,
+                repo_name,
+                ResponseTemplate::new(200).set_body_json(installation),
+            )
+            .await;
+        }
+        // One token is created for all the lib repositories.
+        Mock::given(method("POST"))
+            .and(path("/app/installations/7/access_tokens"))
+            .and(body_json(json!({
+                "repositories": ["lib-a", "lib-b"],
+                "repository_ids": [],
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "token": "ghs_token",
+                "expires_at": "2023-01-01T00:00:00Z",
+                "permissions": {"pull_requests": "read"},
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = get_client_for_repo_from_installations(
+            &mock_octocrab(&server),
+            "org",
+            &[String::from("lib-a"), String::from("lib-b")],
+        )
+        .await;
+
+        assert!(result.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_get_client_for_repo_from_installations_without_repos() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let result = get_client_for_repo_from_installations(&mock_octocrab(&server), "org", &[])
+            .await
+            .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_client_for_repo_from_installations_not_installed() {
+        let server = MockServer::start().await;
+        mock_installation(
+            &server,
+            "lib-a",
+            ResponseTemplate::new(200).set_body_json(installation_json(7, None)),
+        )
+        .await;
+        mock_installation(
+            &server,
+            "lib-b",
+            ResponseTemplate::new(404).set_body_json(json!({
+                "message": "Integration not found",
+                "documentation_url": "https://docs.github.com/rest",
+            })),
+        )
+        .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let result = get_client_for_repo_from_installations(
+            &mock_octocrab(&server),
+            "org",
+            &[String::from("lib-a"), String::from("lib-b")],
+        )
+        .await;
+
+        match result {
+            Err(GithubSetupError::NoInstallation { owner, repo }) => {
+                assert_eq!((owner.as_str(), repo.as_str()), ("org", "lib-b"));
+            }
+            other => panic!("Expected NoInstallation, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_client_for_repo_from_installations_different_installations() {
+        let server = MockServer::start().await;
+        mock_installation(
+            &server,
+            "lib-a",
+            ResponseTemplate::new(200).set_body_json(installation_json(7, None)),
+        )
+        .await;
+        mock_installation(
+            &server,
+            "lib-b",
+            ResponseTemplate::new(200).set_body_json(installation_json(8, None)),
+        )
+        .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let error = get_client_for_repo_from_installations(
+            &mock_octocrab(&server),
+            "org",
+            &[String::from("lib-a"), String::from("lib-b")],
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert!(matches!(
+            error,
+            GithubSetupError::DifferentInstallations { .. }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "org/lib-a and org/lib-b belong to different installations of the app"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_client_for_repo_from_installations_github_error() {
+        let server = MockServer::start().await;
+        mock_installation(
+            &server,
+            "lib-a",
+            ResponseTemplate::new(403).set_body_json(json!({
+                "message": "Not Found",
+                "documentation_url": "https://docs.github.com/rest",
+            })),
+        )
+        .await;
+
+        let error = get_client_for_repo_from_installations(
+            &mock_octocrab(&server),
+            "org",
+            &[String::from("lib-a")],
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert!(matches!(error, GithubSetupError::Github(_)));
+    }
+
     /// Test is ignored because it makes real requests against GitHub API. It should only be run locally.
     /// To run only this test, use command:
     ///
@@ -106,9 +648,15 @@ mod tests {
         let mut contents = Vec::new();
         pk.read_to_end(&mut contents).unwrap();
         let pk = contents.as_slice();
-        let octo = get_octocrab_instance_for_lib_repo(AppId(293643), pk, "km-dep")
-            .await
-            .unwrap();
+        let octo = get_octocrab_instance_for_lib_repo(
+            AppId(293643),
+            pk,
+            "umatbro",
+            &[String::from("km-dep")],
+        )
+        .await
+        .unwrap()
+        .unwrap();
         let repo = octo.repos("umatbro", "km-dep").get().await.unwrap();
         println!("REPO {:?}", repo);
 
@@ -1,111 +1,356 @@
-use crate::cli::JiraLink;
-use crate::github_pull_request::PullRequest;
+//! This package contains code that updates the PR's body.
 
-/// This package contains code that updates the PR's body.
+use similar::TextDiff;
+use std::str::FromStr;
 
-const COMMENT_START: &'static str = "<!-- START KM-ACTION -->";
-const COMMENT_END: &'static str = "<!-- END KM-ACTION -->";
+use crate::github_pull_request::{
+    ticket_error_message, LibRepoPullRequests, PullRequest, Repository, TicketParser,
+};
+use crate::summary_template::{
+    LibRepoContext, PullRequestContext, RepositoryContext, SummaryContext, SummaryTemplate,
+    TicketContext,
+};
+use crate::tracker::TicketDetails;
 
-/// Get a updated body of Pull Request description.
-/// * Add a line saying that Github action modified the body.
-/// * Add a line with found ticket numbers (based on `pull_request` parameter).
-/// * Add a line with found open PRs in the lib repo (based on `lib_repo_pull_requests` parameter).
+const COMMENT_START: &str = "<!-- START KM-ACTION -->";
+const COMMENT_END: &str = "<!-- END KM-ACTION -->";
+/// Where the action section is put when the body doesn't contain the markers yet. Once it's
+/// there, the section stays where it is, even if people move it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Placement {
+    Top,
+    #[default]
+    Bottom,
+    /// Right after the Markdown heading line, e.g. `## Related`. At the bottom if the body has
+    /// no such heading.
+    AfterHeading(String),
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "top" => Ok(Self::Top),
+            "bottom" => Ok(Self::Bottom),
+            heading if heading.starts_with('#') => Ok(Self::AfterHeading(heading.to_string())),
+            other => Err(format!(
+                "Invalid placement: {}, expected top, bottom or a Markdown heading, e.g. ## Related",
+                other
+            )),
+        }
+    }
+}
+
+/// Lib PR bodies are not templated, only the PRs that depend on them are.
+const LIB_UPDATE_HEADER: [&str; 3] = ["---", "### 🤖 This is update from km-action.", ""];
+const DEPENDENT_PRS_PREFIX: &str = "🔗 Dependent PRs in ";
+
+/// Get a updated body of Pull Request description, with the section rendered from the `template`
+/// between the action markers. The default template adds:
+/// * A line saying that Github action modified the body.
+/// * A line with found ticket numbers (based on `pull_request` parameter).
+/// * A section per lib repo with found PRs and their state (based on `lib_repo_pull_requests`
+///   parameter).
 pub fn get_update_body(
     pull_request: &PullRequest,
-    lib_repo_pull_requests: &Vec<PullRequest>,
-    jira_link: &Option<JiraLink>,
-) -> String {
-    let current_body = &pull_request.body;
-    let mut lines: Vec<String> = current_body.lines().map(|i| i.to_string()).collect();
+    repository: &Repository,
+    lib_repo_pull_requests: &[LibRepoPullRequests],
+    ticket_details: &TicketDetails,
+    ticket_parser: &TicketParser,
+    template: &SummaryTemplate,
+    placement: &Placement,
+) -> Result<String, minijinja::Error> {
+    let context = get_summary_context(
+        pull_request,
+        repository,
+        lib_repo_pull_requests,
+        ticket_details,
+        ticket_parser,
+    );
+    let lines_added = template.render(&context)?;
 
-    let mut lines_added = vec![];
+    Ok(replace_lines_assigned_by_action(
+        &pull_request.body,
+        lines_added,
+        placement,
+    ))
+}
 
-    lines_added.push(COMMENT_START.to_string());
-    lines_added.push(String::from("---"));
-    lines_added.push(String::from("### 🤖 This is update from km-action."));
-    lines_added.push(String::from(""));
-    lines_added.push(get_ticket_number_line(pull_request, jira_link));
-    for line in get_lib_prs_lines(pull_request, lib_repo_pull_requests) {
-        lines_added.push(line);
+/// Get the body of the action comment, used instead of the PR description in the comment output
+/// mode. It contains the same section `get_update_body` adds to the description.
+/// Returns `None` if there is nothing to say, i.e. no ticket number was found (lib PRs are
+/// related by ticket numbers too).
+pub fn get_update_comment(
+    pull_request: &PullRequest,
+    repository: &Repository,
+    lib_repo_pull_requests: &[LibRepoPullRequests],
+    ticket_details: &TicketDetails,
+    ticket_parser: &TicketParser,
+    template: &SummaryTemplate,
+) -> Result<Option<String>, minijinja::Error> {
+    let context = get_summary_context(
+        pull_request,
+        repository,
+        lib_repo_pull_requests,
+        ticket_details,
+        ticket_parser,
+    );
+    if context.tickets.is_empty() {
+        return Ok(None);
     }
-    lines_added.push(COMMENT_END.to_string());
+    let lines_added = template.render(&context)?;
 
-    let added_description = find_lines_assigned_by_action(&lines);
+    Ok(Some(replace_lines_assigned_by_action(
+        "",
+        lines_added,
+        &Placement::Bottom,
+    )))
+}
+
+/// Check whether the comment `body` was added by the action.
+pub fn is_assigned_by_action(body: &str) -> bool {
+    body.lines().any(|line| line.trim() == COMMENT_START)
+}
 
-    match added_description {
-        Some(lines_found) => {
-            lines.drain(lines_found.from..=lines_found.to);
-            lines.splice(lines_found.from..lines_found.from, lines_added);
+/// Variables of the summary template for the `pull_request`.
+pub fn get_summary_context(
+    pull_request: &PullRequest,
+    repository: &Repository,
+    lib_repo_pull_requests: &[LibRepoPullRequests],
+    ticket_details: &TicketDetails,
+    ticket_parser: &TicketParser,
+) -> SummaryContext {
+    let (owner, name) = repository.full_name.split_once('/').unwrap_or_default();
+    let (tickets, ticket_error) = match pull_request.get_ticket_number(ticket_parser) {
+        Ok(ticket_numbers) => {
+            let mut ticket_numbers: Vec<String> = ticket_numbers.into_iter().collect();
+            ticket_numbers.sort();
+            let tickets = ticket_numbers
+                .into_iter()
+                .map(|number| {
+                    let lookup = ticket_details.issues.get(&number);
+                    let issue = lookup.cloned().flatten();
+                    TicketContext {
+                        url: ticket_details.ticket_url(&number),
+                        missing: matches!(lookup, Some(None)),
+                        closed: issue.as_ref().is_some_and(|i| i.closed),
+                        summary: issue.as_ref().map(|i| i.summary.clone()),
+                        status: issue.as_ref().map(|i| i.status.clone()),
+                        issue_type: issue.as_ref().map(|i| i.issue_type.clone()),
+                        assignee: issue.and_then(|i| i.assignee),
+                        number,
+                    }
+                })
+                .collect();
+            (tickets, None)
         }
-        None => lines.extend(lines_added),
+        Err(e) => (vec![], Some(ticket_error_message(&e))),
+    };
+
+    SummaryContext {
+        repository: RepositoryContext {
+            full_name: repository.full_name.clone(),
+            owner: owner.to_string(),
+            name: name.to_string(),
+        },
+        pull_request: get_pull_request_context(pull_request),
+        tickets,
+        ticket_error,
+        lib_repos: get_lib_repos_context(pull_request, lib_repo_pull_requests, ticket_parser),
     }
+}
 
-    let mut result = lines.join("\n");
-    if !(result.ends_with("\n")) {
-        result.push_str("\n");
+/// Get a updated body of lib Pull Request description.
+/// * Add a line saying that Github action modified the body.
+/// * Add a section with PRs from the main repository that share ticket numbers with the lib PR
+///   (based on `main_pull_requests` parameter).
+///
+/// A lib PR can be linked from multiple main repositories, each one has its own subsection. Only
+/// the subsection of `main_repo_full_name` is replaced, the others are kept as they are.
+pub fn get_lib_update_body(
+    lib_pull_request: &PullRequest,
+    main_repo_full_name: &str,
+    main_pull_requests: &[PullRequest],
+    ticket_parser: &TicketParser,
+) -> String {
+    let dependent_prs: Vec<String> =
+        get_related_pull_requests(lib_pull_request, main_pull_requests, ticket_parser)
+            .into_iter()
+            .map(|pull_request| {
+                let pr_link = pull_request.html_url.as_ref().unwrap();
+                format!("* {} ({})", pr_link, pull_request.status().marker())
+            })
+            .collect();
+    let mut sections = get_dependent_prs_sections(&lib_pull_request.body);
+    match sections
+        .iter_mut()
+        .find(|(repo_full_name, _)| repo_full_name == main_repo_full_name)
+    {
+        Some((_, items)) => *items = dependent_prs,
+        None => sections.push((main_repo_full_name.to_string(), dependent_prs)),
     }
 
-    result
+    let mut lines_added: Vec<String> = LIB_UPDATE_HEADER.iter().map(|l| l.to_string()).collect();
+    for (index, (repo_full_name, items)) in sections.into_iter().enumerate() {
+        if index > 0 {
+            lines_added.push(String::new());
+        }
+        lines_added.push(format!("{}{}:", DEPENDENT_PRS_PREFIX, repo_full_name));
+        lines_added.extend(items);
+    }
+
+    replace_lines_assigned_by_action(&lib_pull_request.body, lines_added, &Placement::Bottom)
 }
 
-fn get_ticket_number_line(pull_request: &PullRequest, jira_link: &Option<JiraLink>) -> String {
-    let ticket_numbers = pull_request.get_ticket_number();
-    if let Err(_) = ticket_numbers {
-        return String::from("❓Ticket number: **Not Found**");
+/// Dependent PRs subsections of the action section in the lib PR `body`, in order: the full name
+/// of the main repository and the list items of its PRs.
+fn get_dependent_prs_sections(body: &str) -> Vec<(String, Vec<String>)> {
+    let lines: Vec<String> = body.lines().map(String::from).collect();
+    let mut sections: Vec<(String, Vec<String>)> = vec![];
+    for assigned in find_lines_assigned_by_action(&lines) {
+        for line in lines[assigned.from..assigned.to]
+            .iter()
+            .map(|line| line.trim())
+        {
+            let heading = line
+                .strip_prefix(DEPENDENT_PRS_PREFIX)
+                .and_then(|rest| rest.strip_suffix(':'));
+            match (heading, sections.last_mut()) {
+                (Some(repo_full_name), _) => sections.push((repo_full_name.to_string(), vec![])),
+                (None, Some((_, items))) if line.starts_with("* ") => items.push(line.to_string()),
+                (None, _) => {}
+            }
+        }
     }
-    let mut result = String::from("✅ Ticket number: **");
-    let mut ticket_numbers = ticket_numbers
-        .unwrap()
-        .into_iter()
-        .map(|tick_num| match jira_link {
-            None => tick_num,
-            Some(jl) => format!("[{}]({})", tick_num, jl.ticket_url(&tick_num)),
+    sections
+}
+
+/// Put `content` between the action markers. If the `body` already contains the markers, the
+/// lines between them are replaced, otherwise the section is inserted at the `placement`.
+///
+/// Duplicate sections and unmatched markers (e.g. left after someone edited the body) are removed,
+/// the section is put where the first of them was. Line endings of the body are kept, the added
+/// lines use `\r\n` if the body does (bodies written in the GitHub web UI do).
+fn replace_lines_assigned_by_action(
+    body: &str,
+    content: Vec<String>,
+    placement: &Placement,
+) -> String {
+    let newline = match body.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+    let mut lines: Vec<String> = body
+        .split_inclusive('\n')
+        .map(|line| match line.ends_with('\n') {
+            true => line.to_string(),
+            false => format!("{}{}", line, newline),
         })
-        .collect::<Vec<String>>();
-    ticket_numbers.sort();
-    let ticket_numbers_str = ticket_numbers.join(", ");
-    result.push_str(&ticket_numbers_str);
-    result.push_str("**");
+        .collect();
 
-    result
+    let mut lines_added = vec![COMMENT_START.to_string()];
+    lines_added.extend(content);
+    lines_added.push(COMMENT_END.to_string());
+    let lines_added = lines_added
+        .into_iter()
+        .map(|line| format!("{}{}", line, newline));
+
+    let added_description = find_lines_assigned_by_action(&lines);
+    let index = match added_description.first() {
+        Some(lines_found) => lines_found.from,
+        None => match placement {
+            Placement::Top => 0,
+            Placement::Bottom => lines.len(),
+            Placement::AfterHeading(heading) => lines
+                .iter()
+                .position(|line| line.trim() == heading)
+                .map_or(lines.len(), |index| index + 1),
+        },
+    };
+    // From the last one, so the indices of the others stay valid.
+    for lines_found in added_description.iter().rev() {
+        lines.drain(lines_found.from..=lines_found.to);
+    }
+    lines.splice(index..index, lines_added);
+
+    lines.concat()
 }
 
-fn get_lib_prs_lines(
+fn get_pull_request_context(pull_request: &PullRequest) -> PullRequestContext {
+    let status = pull_request.status();
+    PullRequestContext {
+        number: pull_request.number,
+        title: pull_request.title.clone(),
+        url: pull_request.html_url.clone(),
+        branch: pull_request.head.ref_name.clone(),
+        status: status.name().to_string(),
+        marker: status.marker().to_string(),
+    }
+}
+
+/// Lib PRs related to the `main_pull_request`, grouped by the lib repository. Repositories
+/// without related PRs are skipped.
+fn get_lib_repos_context(
     main_pull_request: &PullRequest,
-    lib_pull_requests: &Vec<PullRequest>,
-) -> Vec<String> {
-    let main_pr_ticket_nums = main_pull_request.get_ticket_number();
-    if let Err(_) = main_pr_ticket_nums {
+    lib_repos_pull_requests: &[LibRepoPullRequests],
+    ticket_parser: &TicketParser,
+) -> Vec<LibRepoContext> {
+    lib_repos_pull_requests
+        .iter()
+        .map(|lib_repo| LibRepoContext {
+            full_name: lib_repo.repo_full_name.clone(),
+            pull_requests: get_related_pull_requests(
+                main_pull_request,
+                &lib_repo.pull_requests,
+                ticket_parser,
+            )
+            .into_iter()
+            .map(get_pull_request_context)
+            .collect(),
+        })
+        .filter(|lib_repo| !lib_repo.pull_requests.is_empty())
+        .collect()
+}
+
+/// Find pull requests among `candidates` which share a ticket number with the `pull_request`.
+/// Candidates without `html_url` are skipped, as they can't be linked.
+pub fn get_related_pull_requests<'a>(
+    pull_request: &PullRequest,
+    candidates: &'a [PullRequest],
+    ticket_parser: &TicketParser,
+) -> Vec<&'a PullRequest> {
+    let ticket_nums = pull_request.get_ticket_number(ticket_parser);
+    if ticket_nums.is_err() {
         return vec![];
     }
-    let main_pr_ticket_nums = main_pr_ticket_nums.unwrap();
-    let matching_lib_pull_requests: Vec<&PullRequest> = lib_pull_requests
+    let ticket_nums = ticket_nums.unwrap();
+
+    candidates
         .iter()
+        .filter(|pr| pr.html_url.is_some())
         .filter(|pr| {
-            let ticket_numbers = pr.get_ticket_number();
-            if let Err(_) = ticket_numbers {
+            let ticket_numbers = pr.get_ticket_number(ticket_parser);
+            if ticket_numbers.is_err() {
                 return false;
             }
             let ticket_numbers = ticket_numbers.unwrap();
-            // if ticket numbers from lib PR intersect with ticket numbers in main repo - this PR should be included.
-            return !ticket_numbers.is_disjoint(&main_pr_ticket_nums);
+            // if ticket numbers from the candidate intersect with ticket numbers of the pull request - it should be included.
+            !ticket_numbers.is_disjoint(&ticket_nums)
         })
-        .collect();
-    if matching_lib_pull_requests.len() == 0 {
-        return vec![];
-    }
+        .collect()
+}
 
-    let mut lines = Vec::with_capacity(matching_lib_pull_requests.len() + 2);
-    lines.push(String::from(""));
-    lines.push(String::from("📦 Related lib PRs:"));
-    for lib_pull_request in matching_lib_pull_requests
-        .into_iter()
-        .filter(|pr| pr.html_url.is_some())
-    {
-        let pr_link = lib_pull_request.html_url.as_ref().unwrap();
-        lines.push(format!("* {}", pr_link));
-    }
-    lines
+/// Unified diff between the current and the updated body, named after the PR `name`.
+/// Empty if the body does not change.
+pub fn get_body_diff(name: &str, body: &str, updated_body: &str) -> String {
+    TextDiff::from_lines(body, updated_body)
+        .unified_diff()
+        .header(
+            &format!("{} (current)", name),
+            &format!("{} (updated)", name),
+        )
+        .to_string()
 }
 
 struct LinesAssignedByAction {
@@ -116,43 +361,71 @@ struct LinesAssignedByAction {
 /// The action adds text to a PR body. This text is wrapped in distinct tags (see `COMMENT_START`
 /// and `COMMENT_END` const values).
 /// The `find_lines_assigned_by_action` identifies those lines and returns indices of lines containing
-/// contents added automatically by the action.
-fn find_lines_assigned_by_action(lines: &Vec<String>) -> Option<LinesAssignedByAction> {
-    let mut iter = lines.into_iter().enumerate();
+/// contents added automatically by the action, in order. Markers are matched regardless of the
+/// surrounding whitespace. A marker without its pair is returned alone, so it can be removed
+/// without touching the lines around it, which may have been written by people.
+fn find_lines_assigned_by_action(lines: &[String]) -> Vec<LinesAssignedByAction> {
+    let mut result = vec![];
     let mut first_line_index = None;
-    let mut last_line_index = None;
 
-    while let Some(item) = iter.next() {
-        let (index, line) = item;
-        if line.eq(COMMENT_START) {
-            first_line_index = Some(index);
-            break;
+    for (index, line) in lines.iter().enumerate() {
+        match line.trim() {
+            COMMENT_START => {
+                if let Some(from) = first_line_index.replace(index) {
+                    result.push(LinesAssignedByAction { from, to: from });
+                }
+            }
+            COMMENT_END => {
+                let from = first_line_index.take().unwrap_or(index);
+                result.push(LinesAssignedByAction { from, to: index });
+            }
+            _ => {}
         }
     }
-    while let Some(item) = iter.next() {
-        let (index, line) = item;
-        if line.eq(COMMENT_END) {
-            last_line_index = Some(index);
-            break;
-        }
+    if let Some(from) = first_line_index {
+        result.push(LinesAssignedByAction { from, to: from });
     }
 
-    match (first_line_index, last_line_index) {
-        (Some(from), Some(to)) => Some(LinesAssignedByAction { from, to }),
-        _ => None,
-    }
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cli::JiraLink;
-    use crate::description_manipulator::{find_lines_assigned_by_action, get_update_body};
-    use crate::github_pull_request::PullRequest;
+    use crate::description_manipulator::{
+        find_lines_assigned_by_action, get_body_diff, get_lib_update_body,
+        get_related_pull_requests, get_summary_context, get_update_body, get_update_comment,
+        is_assigned_by_action, replace_lines_assigned_by_action, Placement, COMMENT_END,
+        COMMENT_START,
+    };
+    use crate::github_pull_request::{
+        Head, LibRepoPullRequests, PullRequest, PullRequestState, Repository, TicketParser,
+        TicketSource,
+    };
+    use crate::summary_template::SummaryTemplate;
+    use crate::tracker::{TicketDetails, TrackerIssue, TrackerMapping, Trackers};
+    use chrono::Utc;
+    use proptest::prelude::*;
     use rstest::rstest;
+    use std::collections::HashMap;
     use std::fs::File;
     use std::io::Read;
     use std::path::Path;
 
+    fn repository() -> Repository {
+        Repository::from_full_name("umatbro/km-action").unwrap()
+    }
+
+    fn jira_issue(assignee: Option<&str>) -> TrackerIssue {
+        TrackerIssue {
+            summary: String::from("Fix login timeout"),
+            status: String::from("In Review"),
+            issue_type: String::from("Bug"),
+            assignee: assignee.map(String::from),
+            closed: false,
+        }
+    }
+
     fn read_test_file_content(file_name: &str) -> String {
         let path = Path::new("src/test_resources").join(file_name);
         let mut f = File::open(path).unwrap();
@@ -198,42 +471,663 @@ mod tests {
             body: data_in,
             title: pull_request_title.to_string(),
             html_url: None,
+            ..Default::default()
         };
 
-        let lib_pull_requests: Vec<PullRequest> = lib_pull_requests_titles
-            .iter()
-            .map(|title| PullRequest {
-                title: title.clone(),
-                html_url: Some(String::from("https://test.com")),
+        let lib_pull_requests = vec![LibRepoPullRequests {
+            repo_full_name: String::from("umatbro/km-lib"),
+            pull_requests: lib_pull_requests_titles
+                .iter()
+                .map(|title| PullRequest {
+                    title: title.clone(),
+                    html_url: Some(String::from("https://test.com")),
+                    ..Default::default()
+                })
+                .collect(),
+        }];
+        let trackers = Trackers::new(&[], jira_host.as_ref(), None, "umatbro/km-action");
+        let result = get_update_body(
+            &pull_request,
+            &repository(),
+            &lib_pull_requests,
+            &TicketDetails {
+                trackers: Some(&trackers),
                 ..Default::default()
-            })
-            .collect();
-        let result = get_update_body(&pull_request, &lib_pull_requests, &jira_host);
+            },
+            &TicketParser::default(),
+            &SummaryTemplate::default(),
+            &Placement::Bottom,
+        )
+        .unwrap();
         assert_eq!(data_out, result);
     }
 
+    #[test]
+    fn test_get_summary_context() {
+        let pull_request = PullRequest {
+            number: 7,
+            title: String::from("[MD-3][BACK-12] Title"),
+            head: Head {
+                ref_name: String::from("feature/x"),
+                ..Default::default()
+            },
+            draft: true,
+            ..Default::default()
+        };
+        // MD tickets are tracked in Linear, the rest in the default Jira.
+        let trackers = Trackers::new(
+            &TrackerMapping::parse_list("MD=linear:acme").unwrap(),
+            Some(&JiraLink::parse("https://test.com").unwrap()),
+            None,
+            "umatbro/km-action",
+        );
+        let ticket_details = TicketDetails {
+            trackers: Some(&trackers),
+            issues: HashMap::from([(String::from("MD-3"), Some(jira_issue(None)))]),
+        };
+
+        let context = get_summary_context(
+            &pull_request,
+            &repository(),
+            &[],
+            &ticket_details,
+            &TicketParser::default(),
+        );
+
+        assert_eq!(context.repository.owner, "umatbro");
+        assert_eq!(context.repository.name, "km-action");
+        assert_eq!(context.pull_request.number, 7);
+        assert_eq!(context.pull_request.branch, "feature/x");
+        assert_eq!(context.pull_request.status, "draft");
+        let tickets: Vec<(&str, Option<&str>, Option<&str>)> = context
+            .tickets
+            .iter()
+            .map(|t| (t.number.as_str(), t.url.as_deref(), t.status.as_deref()))
+            .collect();
+        assert_eq!(
+            tickets,
+            vec![
+                ("BACK-12", Some("https://test.com/browse/BACK-12"), None),
+                (
+                    "MD-3",
+                    Some("https://linear.app/acme/issue/MD-3"),
+                    Some("In Review")
+                ),
+            ]
+        );
+        assert!(context.ticket_error.is_none());
+        assert!(context.lib_repos.is_empty());
+
+        let pull_request = PullRequest {
+            title: String::from("No ticket"),
+            ..Default::default()
+        };
+        let context = get_summary_context(
+            &pull_request,
+            &repository(),
+            &[],
+            &TicketDetails::default(),
+            &TicketParser::default(),
+        );
+        assert!(context.tickets.is_empty());
+        assert!(context.ticket_error.unwrap().contains("ticket number"));
+    }
+
+    #[test]
+    fn test_get_update_body_with_jira_issues() {
+        let pull_request = PullRequest {
+            title: String::from("[BACK-42][BACK-43][BACK-44][BACK-45][BACK-46] Title"),
+            ..Default::default()
+        };
+        let trackers = Trackers::new(
+            &[],
+            Some(&JiraLink::parse("https://test.com").unwrap()),
+            None,
+            "umatbro/km-action",
+        );
+        let ticket_details = TicketDetails {
+            trackers: Some(&trackers),
+            issues: HashMap::from([
+                (String::from("BACK-42"), Some(jira_issue(Some("alice")))),
+                (String::from("BACK-43"), Some(jira_issue(None))),
+                (String::from("BACK-44"), None),
+                (
+                    String::from("BACK-46"),
+                    Some(TrackerIssue {
+                        status: String::from("Done"),
+                        closed: true,
+                        ..jira_issue(None)
+                    }),
+                ),
+            ]),
+        };
+
+        let result = get_update_body(
+            &pull_request,
+            &repository(),
+            &[],
+            &ticket_details,
+            &TicketParser::default(),
+            &SummaryTemplate::default(),
+            &Placement::Bottom,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.lines().nth(4).unwrap(),
+            "✅ Ticket number: **[BACK-42](https://test.com/browse/BACK-42) — Fix login timeout \
+(In Review, @alice), [BACK-43](https://test.com/browse/BACK-43) — Fix login timeout (In Review), \
+[BACK-44](https://test.com/browse/BACK-44) ⚠️ not found in tracker, \
+[BACK-45](https://test.com/browse/BACK-45), [BACK-46](https://test.com/browse/BACK-46) — Fix login \
+timeout (Done) ⚠️ closed**"
+        );
+    }
+
+    #[test]
+    fn test_get_update_body_custom_template() {
+        let pull_request = PullRequest {
+            title: String::from("[BACK-1] Title"),
+            body: String::from("Description"),
+            ..Default::default()
+        };
+        let template = SummaryTemplate::new(
+            String::from(
+                "<details><summary>{{ repository.name }}</summary>\n\n\
+{% for ticket in tickets %}- {{ ticket.number }}\n{% endfor %}</details>\n",
+            ),
+            None,
+        )
+        .unwrap();
+
+        let result = get_update_body(
+            &pull_request,
+            &repository(),
+            &[],
+            &TicketDetails::default(),
+            &TicketParser::default(),
+            &template,
+            &Placement::Bottom,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            "Description
+<!-- START KM-ACTION -->
+<details><summary>km-action</summary>
+
+- BACK-1
+</details>
+<!-- END KM-ACTION -->
+"
+        );
+    }
+
+    #[test]
+    fn test_get_update_body_multiple_lib_repos() {
+        let lib_repo = |full_name: &str, titles: &[&str]| LibRepoPullRequests {
+            repo_full_name: full_name.to_string(),
+            pull_requests: titles
+                .iter()
+                .enumerate()
+                .map(|(i, title)| PullRequest {
+                    title: title.to_string(),
+                    html_url: Some(format!("https://github.com/{}/pull/{}", full_name, i)),
+                    state: match i {
+                        0 => PullRequestState::Open,
+                        _ => PullRequestState::Closed,
+                    },
+                    merged_at: (i > 0).then(Utc::now),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let pull_request = PullRequest {
+            title: String::from("[BACK-1][BACK-2] Main PR"),
+            ..Default::default()
+        };
+        let lib_repos = vec![
+            lib_repo("org/lib-a", &["[BACK-1] A", "[BACK-3] Other"]),
+            lib_repo("org/lib-b", &["[MD-1] Unrelated"]),
+            lib_repo("other-org/lib-c", &["No ticket", "[BACK-2] C"]),
+        ];
+
+        let result = get_update_body(
+            &pull_request,
+            &repository(),
+            &lib_repos,
+            &TicketDetails::default(),
+            &TicketParser::default(),
+            &SummaryTemplate::default(),
+            &Placement::Bottom,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.lines().skip(4).collect::<Vec<&str>>(),
+            vec![
+                "✅ Ticket number: **BACK-1, BACK-2**",
+                "",
+                "📦 Related lib PRs in org/lib-a:",
+                "* https://github.com/org/lib-a/pull/0 (🟢 open)",
+                "",
+                "📦 Related lib PRs in other-org/lib-c:",
+                "* https://github.com/other-org/lib-c/pull/1 (🟣 merged)",
+                "<!-- END KM-ACTION -->",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_related_pull_requests_by_branch() {
+        let pull_request = |number: u64, title: &str, branch: &str| PullRequest {
+            number,
+            title: title.to_string(),
+            html_url: Some(String::from("https://test.com")),
+            head: Head {
+                ref_name: branch.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let main_pull_request = pull_request(0, "fix typo", "feature/BACK-7-fix-typo");
+        let candidates = vec![
+            pull_request(1, "[BACK-7] Titled", "main"),
+            pull_request(2, "Untitled", "BACK-7-lib"),
+            pull_request(3, "Untitled", "BACK-8-lib"),
+        ];
+
+        let ticket_parser = TicketParser {
+            sources: vec![TicketSource::Title, TicketSource::Branch],
+            ..Default::default()
+        };
+
+        let result = get_related_pull_requests(&main_pull_request, &candidates, &ticket_parser);
+
+        let numbers: Vec<u64> = result.iter().map(|pr| pr.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_get_lib_update_body() {
+        let main_pull_request = |number: u64, title: &str| PullRequest {
+            number,
+            title: title.to_string(),
+            html_url: Some(format!("https://github.com/org/main/pull/{}", number)),
+            ..Default::default()
+        };
+        let lib_pull_request = PullRequest {
+            title: String::from("[BACK-1] Lib change"),
+            body: String::from(
+                "Lib description.\n\n<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\n",
+            ),
+            ..Default::default()
+        };
+        let main_pull_requests = vec![
+            main_pull_request(1, "[BACK-1] Uses lib change"),
+            main_pull_request(2, "[BACK-2] Unrelated"),
+            main_pull_request(3, "[MD-1][BACK-1] Also uses lib change"),
+        ];
+
+        let result = get_lib_update_body(
+            &lib_pull_request,
+            "org/main",
+            &main_pull_requests,
+            &TicketParser::default(),
+        );
+
+        assert_eq!(
+            result,
+            "Lib description.
+
+<!-- START KM-ACTION -->
+---
+### 🤖 This is update from km-action.
+
+🔗 Dependent PRs in org/main:
+* https://github.com/org/main/pull/1 (🟢 open)
+* https://github.com/org/main/pull/3 (🟢 open)
+<!-- END KM-ACTION -->
+"
+        );
+    }
+
+    #[test]
+    fn test_get_lib_update_body_multiple_main_repos() {
+        let main_pull_request = |repo: &str, number: u64, state: PullRequestState| PullRequest {
+            number,
+            title: String::from("[BACK-1] Uses lib change"),
+            html_url: Some(format!("https://github.com/{}/pull/{}", repo, number)),
+            state,
+            ..Default::default()
+        };
+        let mut lib_pull_request = PullRequest {
+            title: String::from("[BACK-1] Lib change"),
+            body: String::from("Lib description.\n\n"),
+            ..Default::default()
+        };
+        let ticket_parser = TicketParser::default();
+
+        lib_pull_request.body = get_lib_update_body(
+            &lib_pull_request,
+            "org/main",
+            &[main_pull_request("org/main", 1, PullRequestState::Open)],
+            &ticket_parser,
+        );
+        lib_pull_request.body = get_lib_update_body(
+            &lib_pull_request,
+            "org/other",
+            &[main_pull_request("org/other", 7, PullRequestState::Open)],
+            &ticket_parser,
+        );
+        let result = get_lib_update_body(
+            &lib_pull_request,
+            "org/main",
+            &[
+                main_pull_request("org/main", 1, PullRequestState::Closed),
+                main_pull_request("org/main", 2, PullRequestState::Open),
+            ],
+            &ticket_parser,
+        );
+
+        assert_eq!(
+            result,
+            "Lib description.
+
+<!-- START KM-ACTION -->
+---
+### 🤖 This is update from km-action.
+
+🔗 Dependent PRs in org/main:
+* https://github.com/org/main/pull/1 (🔴 closed)
+* https://github.com/org/main/pull/2 (🟢 open)
+
+🔗 Dependent PRs in org/other:
+* https://github.com/org/other/pull/7 (🟢 open)
+<!-- END KM-ACTION -->
+"
+        );
+    }
+
     #[rstest]
-    #[case("data1_in.md", None)]
-    #[case("data1_out.md", Some((0, 5)))]
-    #[case("data2_in.md", None)]
-    #[case("data2_out.md", Some((1, 6)))]
-    #[case("data3_in.md", Some((2, 5)))]
+    #[case("data1_in.md", vec![])]
+    #[case("data1_out.md", vec![(0, 5)])]
+    #[case("data2_in.md", vec![])]
+    #[case("data2_out.md", vec![(1, 6)])]
+    #[case("data3_in.md", vec![(2, 5)])]
     fn test_find_lines_assigned_by_action(
         #[case] data_in: &str,
-        #[case] line_from_to: Option<(usize, usize)>,
+        #[case] lines_from_to: Vec<(usize, usize)>,
     ) {
         let lines: Vec<String> = read_test_file_content(data_in)
             .lines()
             .map(|i| i.to_string())
             .collect();
         let result = find_lines_assigned_by_action(&lines);
-        match line_from_to {
-            None => assert!(result.is_none()),
-            Some((expected_from, expected_to)) => {
-                assert!(result.is_some());
-                let result = result.unwrap();
-                assert_eq!(expected_from, result.from);
-                assert_eq!(expected_to, result.to);
+        let result: Vec<(usize, usize)> = result.iter().map(|l| (l.from, l.to)).collect();
+        assert_eq!(result, lines_from_to);
+    }
+
+    #[rstest]
+    #[case(&["  <!-- START KM-ACTION -->\r\n", "a", "<!-- END KM-ACTION -->\t"], vec![(0, 2)])]
+    #[case(
+        &["<!-- START KM-ACTION -->", "a", "<!-- END KM-ACTION -->", "b", "<!-- START KM-ACTION -->", "<!-- END KM-ACTION -->"],
+        vec![(0, 2), (4, 5)]
+    )]
+    #[case(&["<!-- START KM-ACTION -->", "a", "<!-- START KM-ACTION -->", "b", "<!-- END KM-ACTION -->"], vec![(0, 0), (2, 4)])]
+    #[case(&["a", "<!-- START KM-ACTION -->", "b"], vec![(1, 1)])]
+    #[case(&["a", "<!-- END KM-ACTION -->", "b"], vec![(1, 1)])]
+    #[case(&["Quoted: <!-- START KM-ACTION -->"], vec![])]
+    fn test_find_lines_assigned_by_action_edited(
+        #[case] lines: &[&str],
+        #[case] lines_from_to: Vec<(usize, usize)>,
+    ) {
+        let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        let result = find_lines_assigned_by_action(&lines);
+        let result: Vec<(usize, usize)> = result.iter().map(|l| (l.from, l.to)).collect();
+        assert_eq!(result, lines_from_to);
+    }
+
+    #[rstest]
+    #[case(
+        "Intro\r\n<!-- START KM-ACTION -->\r\nold\r\n<!-- END KM-ACTION -->\r\nNotes",
+        "Intro\r\n<!-- START KM-ACTION -->\r\nnew\r\n<!-- END KM-ACTION -->\r\nNotes\r\n"
+    )]
+    #[case(
+        "Intro\n <!-- START KM-ACTION --> \nold\n<!-- END KM-ACTION -->\nNotes\n",
+        "Intro\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\nNotes\n"
+    )]
+    #[case(
+        "Intro\n<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\nNotes\n<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\n",
+        "Intro\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\nNotes\n"
+    )]
+    #[case(
+        "Intro\n<!-- START KM-ACTION -->\nNotes\n<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\n",
+        "Intro\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\nNotes\n"
+    )]
+    fn test_replace_lines_assigned_by_action_edited(#[case] body: &str, #[case] expected: &str) {
+        let result =
+            replace_lines_assigned_by_action(body, vec![String::from("new")], &Placement::Bottom);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_get_body_diff() {
+        let diff = get_body_diff(
+            "org/app#1",
+            "Description\nold line\n",
+            "Description\nnew line\n",
+        );
+
+        assert_eq!(
+            diff,
+            "--- org/app#1 (current)
++++ org/app#1 (updated)
+@@ -1,2 +1,2 @@
+ Description
+-old line
++new line
+"
+        );
+        assert_eq!(get_body_diff("org/app#1", "Same\n", "Same\n"), "");
+    }
+
+    #[test]
+    fn test_get_update_comment() {
+        let pull_request = PullRequest {
+            title: String::from("[BACK-1] Title"),
+            body: String::from("Description written by people."),
+            ..Default::default()
+        };
+        let lib_repo_pull_requests = vec![LibRepoPullRequests {
+            repo_full_name: String::from("umatbro/km-lib"),
+            pull_requests: vec![PullRequest {
+                title: String::from("[BACK-1] Lib"),
+                html_url: Some(String::from("https://test.com/lib/1")),
+                ..Default::default()
+            }],
+        }];
+
+        let comment = get_update_comment(
+            &pull_request,
+            &repository(),
+            &lib_repo_pull_requests,
+            &TicketDetails::default(),
+            &TicketParser::default(),
+            &SummaryTemplate::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            comment,
+            "<!-- START KM-ACTION -->
+---
+### 🤖 This is update from km-action.
+
+✅ Ticket number: **BACK-1**
+
+📦 Related lib PRs in umatbro/km-lib:
+* https://test.com/lib/1 (🟢 open)
+<!-- END KM-ACTION -->
+"
+        );
+        assert!(is_assigned_by_action(&comment));
+        assert!(!is_assigned_by_action(&pull_request.body));
+    }
+
+    #[test]
+    fn test_get_update_comment_without_ticket() {
+        let pull_request = PullRequest {
+            title: String::from("No ticket"),
+            ..Default::default()
+        };
+
+        let comment = get_update_comment(
+            &pull_request,
+            &repository(),
+            &[],
+            &TicketDetails::default(),
+            &TicketParser::default(),
+            &SummaryTemplate::default(),
+        )
+        .unwrap();
+
+        assert!(comment.is_none());
+    }
+
+    #[rstest]
+    #[case(
+        "Intro\n\n## Related\n\nNotes",
+        Placement::Top,
+        "<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\nIntro\n\n## Related\n\nNotes\n"
+    )]
+    #[case(
+        "Intro\n\n## Related\n\nNotes",
+        Placement::Bottom,
+        "Intro\n\n## Related\n\nNotes\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\n"
+    )]
+    #[case(
+        "Intro\n\n## Related \n\nNotes",
+        Placement::AfterHeading(String::from("## Related")),
+        "Intro\n\n## Related \n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\n\nNotes\n"
+    )]
+    #[case(
+        "Intro",
+        Placement::AfterHeading(String::from("## Related")),
+        "Intro\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\n"
+    )]
+    #[case(
+        "",
+        Placement::Top,
+        "<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\n"
+    )]
+    // The section stays where it is once it's added.
+    #[case(
+        "Intro\n<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\nNotes",
+        Placement::Top,
+        "Intro\n<!-- START KM-ACTION -->\nnew\n<!-- END KM-ACTION -->\nNotes\n"
+    )]
+    fn test_replace_lines_assigned_by_action_placement(
+        #[case] body: &str,
+        #[case] placement: Placement,
+        #[case] expected: &str,
+    ) {
+        let result = replace_lines_assigned_by_action(body, vec![String::from("new")], &placement);
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case("top", Ok(Placement::Top))]
+    #[case(" bottom ", Ok(Placement::Bottom))]
+    #[case("## Related", Ok(Placement::AfterHeading(String::from("## Related"))))]
+    #[case("middle", Err(()))]
+    fn test_parse_placement(#[case] input: &str, #[case] expected: Result<Placement, ()>) {
+        assert_eq!(input.parse::<Placement>().map_err(|_| ()), expected);
+    }
+
+    /// A body line: a marker (possibly indented), a heading or text, with one of the line endings.
+    fn body_line() -> impl Strategy<Value = String> {
+        let text = prop_oneof![
+            Just(String::from("<!-- START KM-ACTION -->")),
+            Just(String::from("<!-- END KM-ACTION -->")),
+            Just(String::from("  <!-- START KM-ACTION --> ")),
+            Just(String::from("\t<!-- END KM-ACTION -->")),
+            Just(String::from("## Related")),
+            Just(String::new()),
+            "[a-z #*-]{0,12}",
+        ];
+        let ending = prop_oneof![Just(""), Just("\n"), Just("\r\n")];
+        (text, ending).prop_map(|(text, ending)| format!("{}{}", text, ending))
+    }
+
+    fn body() -> impl Strategy<Value = String> {
+        prop::collection::vec(body_line(), 0..12).prop_map(|lines| lines.concat())
+    }
+
+    fn content() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec("[a-z #*-]{0,12}", 0..4)
+    }
+
+    fn placement() -> impl Strategy<Value = Placement> {
+        prop_oneof![
+            Just(Placement::Top),
+            Just(Placement::Bottom),
+            Just(Placement::AfterHeading(String::from("## Related"))),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_replace_lines_assigned_by_action_is_idempotent(
+            body in body(),
+            content in content(),
+            placement in placement(),
+        ) {
+            let once = replace_lines_assigned_by_action(&body, content.clone(), &placement);
+            let twice = replace_lines_assigned_by_action(&once, content, &placement);
+
+            prop_assert_eq!(&twice, &once);
+            let markers: Vec<&str> = once
+                .lines()
+                .map(str::trim)
+                .filter(|line| *line == COMMENT_START || *line == COMMENT_END)
+                .collect();
+            prop_assert_eq!(markers, vec![COMMENT_START, COMMENT_END]);
+        }
+
+        #[test]
+        fn test_replace_lines_assigned_by_action_keeps_position(
+            body in body(),
+            content in content(),
+            other_content in content(),
+            placement in placement(),
+        ) {
+            let once = replace_lines_assigned_by_action(&body, other_content, &placement);
+            let updated = replace_lines_assigned_by_action(&once, content.clone(), &Placement::Top);
+
+            prop_assert_eq!(
+                updated,
+                replace_lines_assigned_by_action(&body, content, &placement)
+            );
+        }
+
+        #[test]
+        fn test_replace_lines_assigned_by_action_keeps_line_endings(
+            body in body(),
+            content in content(),
+        ) {
+            let result = replace_lines_assigned_by_action(&body, content, &Placement::Bottom);
+
+            prop_assert!(result.ends_with('\n'));
+            let crlf_only = |text: &str| text.matches('\n').count() == text.matches("\r\n").count();
+            if body.contains("\r\n") && crlf_only(&body) {
+                prop_assert!(crlf_only(&result));
+            }
+            if !body.contains("\r\n") {
+                prop_assert!(!result.contains("\r\n"));
             }
         }
     }
@@ -0,0 +1,204 @@
+//! Errors the action stops with. Each kind of failure has its own exit code, so a failed run can
+//! be told apart without reading the log.
+use std::fmt;
+use std::io;
+use std::process::ExitCode;
+
+use crate::common_lib_handler::GithubSetupError;
+
+#[derive(Debug)]
+pub enum ActionError {
+    /// Invalid inputs or missing environment variables.
+    Config(String),
+    /// The event payload file couldn't be read.
+    Io { path: String, source: io::Error },
+    /// The event payload is not a pull request event.
+    Parse(String),
+    /// The app couldn't be authenticated for the lib repositories of `owner`.
+    Auth {
+        owner: String,
+        source: Box<GithubSetupError>,
+    },
+    /// A GitHub API request failed, `action` describes what was being done.
+    Github {
+        action: String,
+        source: Box<octocrab::Error>,
+    },
+    /// The PR kept changing while its body was updated, it was left as it is.
+    Conflict(String),
+    /// The summary template couldn't be rendered.
+    Template(minijinja::Error),
+    /// Tickets Jira doesn't know or closed ones, e.g. `BACK-99 (not found)`.
+    InvalidTickets(Vec<String>),
+}
+
+impl ActionError {
+    pub fn github(action: impl Into<String>) -> impl FnOnce(octocrab::Error) -> Self {
+        let action = action.into();
+        move |source| Self::Github {
+            action,
+            source: Box::new(source),
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Self::Config(_) => 2,
+            Self::Io { .. } => 3,
+            Self::Parse(_) => 4,
+            Self::Auth { .. } => 5,
+            Self::Github { .. } => 6,
+            Self::Conflict(_) => 7,
+            Self::Template(_) => 8,
+            Self::InvalidTickets(_) => 9,
+        })
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(message) => {
+                let first_line = message.lines().next().unwrap_or_default();
+                write!(f, "Invalid configuration: {}", first_line)
+            }
+            Self::Io { path, source } => write!(f, "Couldn't read {}: {}", path, source),
+            Self::Parse(message) => write!(f, "Invalid event payload: {}", message),
+            Self::Auth { owner, source } => write!(
+                f,
+                "Couldn't authenticate for the lib repositories of {}: {}",
+                owner, source
+            ),
+            Self::Github { action, source } => {
+                write!(f, "{} failed: {}", action, describe_github_error(source))
+            }
+            Self::Conflict(name) => write!(
+                f,
+                "PR {} kept changing while its body was updated, it was left as it is",
+                name
+            ),
+            Self::Template(source) => {
+                write!(f, "Rendering the summary template failed: {}", source)
+            }
+            Self::InvalidTickets(tickets) => write!(f, "Invalid tickets: {}", tickets.join(", ")),
+        }
+    }
+}
+
+/// One line description of the octocrab error. Its `Display` includes the backtrace.
+pub fn describe_github_error(error: &octocrab::Error) -> String {
+    let description = match error {
+        octocrab::Error::GitHub { source, .. } => source.message.clone(),
+        octocrab::Error::Url { source, .. } => format!("invalid URL: {}", source),
+        octocrab::Error::Http { source, .. } => format!("HTTP error: {}", source),
+        octocrab::Error::Serde { source, .. } => format!("invalid response: {}", source),
+        octocrab::Error::Json { source, .. } => {
+            format!("invalid response at {}: {}", source.path(), source.inner())
+        }
+        octocrab::Error::JWT { source, .. } => format!("JWT error: {}", source),
+        octocrab::Error::Other { source, .. } => source.to_string(),
+    };
+    description.lines().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_github_error, ActionError};
+    use crate::common_lib_handler::GithubSetupError;
+    use crate::test_utils::mock_octocrab;
+    use rstest::rstest;
+    use serde_json::json;
+    use std::io;
+    use std::process::ExitCode;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[rstest]
+    #[case(
+        ActionError::Config(String::from(
+            "Missing required input: pass --app-id\nUsage: program"
+        )),
+        "Invalid configuration: Missing required input: pass --app-id",
+        2
+    )]
+    #[case(
+        ActionError::Io {
+            path: String::from("event.json"),
+            source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+        },
+        "Couldn't read event.json: not found",
+        3
+    )]
+    #[case(
+        ActionError::Parse(String::from("missing field `pull_request`")),
+        "Invalid event payload: missing field `pull_request`",
+        4
+    )]
+    #[case(
+        ActionError::Auth {
+            owner: String::from("org"),
+            source: Box::new(GithubSetupError::NoInstallation {
+                owner: String::from("org"),
+                repo: String::from("lib"),
+            }),
+        },
+        "Couldn't authenticate for the lib repositories of org: the app has no installation with access to org/lib",
+        5
+    )]
+    #[case(
+        ActionError::Conflict(String::from("org/app#1")),
+        "PR org/app#1 kept changing while its body was updated, it was left as it is",
+        7
+    )]
+    #[case(
+        ActionError::Template(minijinja::Error::new(
+            minijinja::ErrorKind::UnknownFilter,
+            "filter shout is unknown"
+        )),
+        "Rendering the summary template failed: unknown filter: filter shout is unknown",
+        8
+    )]
+    #[case(
+        ActionError::InvalidTickets(vec![
+            String::from("BACK-99 (not found)"),
+            String::from("MD-1 (closed)"),
+        ]),
+        "Invalid tickets: BACK-99 (not found), MD-1 (closed)",
+        9
+    )]
+    fn test_action_error(#[case] error: ActionError, #[case] message: &str, #[case] exit_code: u8) {
+        assert_eq!(error.to_string(), message);
+        assert_eq!(error.exit_code(), ExitCode::from(exit_code));
+    }
+
+    #[tokio::test]
+    async fn test_github_error_is_one_line() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "message": "Resource not accessible by integration",
+                "documentation_url": "https://docs.github.com/rest",
+            })))
+            .mount(&server)
+            .await;
+        let octo = mock_octocrab(&server);
+        let source = octo
+            .pulls("org", "app")
+            .update(1)
+            .body("body")
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            describe_github_error(&source),
+            "Resource not accessible by integration"
+        );
+        let error = ActionError::github("Updating PR org/app#1")(source);
+        assert_eq!(
+            error.to_string(),
+            "Updating PR org/app#1 failed: Resource not accessible by integration"
+        );
+        assert_eq!(error.exit_code(), ExitCode::from(6));
+    }
+}
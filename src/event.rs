@@ -0,0 +1,416 @@
+//! Events the action is triggered by, read from the `GITHUB_EVENT_PATH` payload.
+//!
+//! <https://docs.github.com/en/actions/using-workflows/events-that-trigger-workflows>
+use octocrab::Octocrab;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::common_lib_handler::get_repo_pull_requests;
+use crate::error::ActionError;
+use crate::github_pull_request::{get_pull_request, PullRequest, Repository};
+
+/// Input of the `workflow_dispatch` event with the number of the PR to update.
+pub const PR_NUMBER_INPUT: &str = "pr_number";
+
+#[derive(Debug)]
+pub enum Event {
+    /// `pull_request` and `pull_request_target`, the payload contains the whole PR.
+    PullRequest(Box<PullRequestEvent>),
+    IssueComment(IssueCommentEvent),
+    WorkflowDispatch(WorkflowDispatchEvent),
+    Push(PushEvent),
+    Schedule(ScheduleEvent),
+    /// Event the action has nothing to do for, with its name.
+    Unsupported(String),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestEvent {
+    /// What happened to the pull request, e.g. `opened`, `edited`, `synchronize`.
+    #[serde(default)]
+    pub action: String,
+    /// Previous values of the fields changed by the `edited` action.
+    #[serde(default)]
+    pub changes: Changes,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub sender: Option<Sender>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Changes {
+    pub title: Option<serde_json::Value>,
+    pub base: Option<serde_json::Value>,
+}
+
+/// User the event was triggered by.
+#[derive(Deserialize, Debug)]
+pub struct Sender {
+    pub login: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IssueCommentEvent {
+    pub issue: Issue,
+    pub repository: Repository,
+    pub sender: Option<Sender>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Issue {
+    pub number: u64,
+    /// Set only for the comments of pull requests.
+    pub pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorkflowDispatchEvent {
+    pub inputs: Option<HashMap<String, serde_json::Value>>,
+    pub repository: Repository,
+    pub sender: Option<Sender>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PushEvent {
+    /// Pushed ref, e.g. `refs/heads/main`.
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub repository: Repository,
+    pub sender: Option<Sender>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScheduleEvent {
+    /// Not part of the scheduled payload, `GITHUB_REPOSITORY` is used instead.
+    pub repository: Option<Repository>,
+}
+
+impl Event {
+    /// Parse the payload of the `event_name` event (`GITHUB_EVENT_NAME`).
+    pub fn parse(event_name: &str, payload: impl Read) -> serde_json::Result<Self> {
+        Ok(match event_name {
+            "pull_request" | "pull_request_target" => {
+                Self::PullRequest(serde_json::from_reader(payload)?)
+            }
+            "issue_comment" => Self::IssueComment(serde_json::from_reader(payload)?),
+            "workflow_dispatch" => Self::WorkflowDispatch(serde_json::from_reader(payload)?),
+            "push" => Self::Push(serde_json::from_reader(payload)?),
+            "schedule" => Self::Schedule(serde_json::from_reader(payload)?),
+            other => Self::Unsupported(other.to_string()),
+        })
+    }
+
+    /// Repository the event comes from, if it is a part of the payload.
+    pub fn repository(&self) -> Option<&Repository> {
+        match self {
+            Self::PullRequest(e) => Some(&e.repository),
+            Self::IssueComment(e) => Some(&e.repository),
+            Self::WorkflowDispatch(e) => Some(&e.repository),
+            Self::Push(e) => Some(&e.repository),
+            Self::Schedule(e) => e.repository.as_ref(),
+            Self::Unsupported(_) => None,
+        }
+    }
+
+    pub fn sender(&self) -> Option<&Sender> {
+        match self {
+            Self::PullRequest(e) => e.sender.as_ref(),
+            Self::IssueComment(e) => e.sender.as_ref(),
+            Self::WorkflowDispatch(e) => e.sender.as_ref(),
+            Self::Push(e) => e.sender.as_ref(),
+            Self::Schedule(_) | Self::Unsupported(_) => None,
+        }
+    }
+
+    /// Reason to skip the event, so the action doesn't trigger itself:
+    /// * events sent by one of the `ignored_senders`, e.g. the bot updating PR bodies,
+    /// * `edited` pull request events which changed neither the title nor the base, the body
+    ///   edits are made by the action itself.
+    pub fn skip_reason(&self, ignored_senders: &[String]) -> Option<String> {
+        if let Some(sender) = self.sender() {
+            if ignored_senders.contains(&sender.login) {
+                return Some(format!("The event was sent by {}.", sender.login));
+            }
+        }
+        match self {
+            Self::PullRequest(e)
+                if e.action == "edited"
+                    && e.changes.title.is_none()
+                    && e.changes.base.is_none() =>
+            {
+                Some(String::from(
+                    "Neither the title nor the base of the pull request was edited.",
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Pull requests of the `repository` the event is about.
+    /// * `pull_request` - the PR from the payload.
+    /// * `issue_comment` - the commented PR, nothing for comments of issues.
+    /// * `workflow_dispatch` - the PR from the `pr_number` input, all open PRs without it.
+    /// * `push` - open PRs of the pushed branch.
+    /// * `schedule` - all open PRs.
+    pub async fn get_pull_requests(
+        &self,
+        octo: &Octocrab,
+        repository: &Repository,
+        page_size: u8,
+        max_pages: u32,
+    ) -> Result<Vec<PullRequest>, ActionError> {
+        let owner = repository.get_owner().map_err(|full_name| {
+            ActionError::Parse(format!("invalid repository: {}", full_name))
+        })?;
+        let list_open = || list_open_pull_requests(octo, &owner, repository, page_size, max_pages);
+
+        match self {
+            Self::PullRequest(e) => Ok(vec![e.pull_request.clone()]),
+            Self::IssueComment(e) if e.issue.pull_request.is_some() => Ok(vec![
+                download_pull_request(octo, &owner, repository, e.issue.number).await?,
+            ]),
+            Self::IssueComment(_) | Self::Unsupported(_) => Ok(vec![]),
+            Self::WorkflowDispatch(e) => match e.pr_number()? {
+                Some(number) => Ok(vec![
+                    download_pull_request(octo, &owner, repository, number).await?,
+                ]),
+                None => list_open().await,
+            },
+            Self::Push(e) => {
+                let branch = e
+                    .ref_name
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(&e.ref_name);
+                let mut pull_requests = list_open().await?;
+                pull_requests.retain(|pr| pr.head.ref_name == branch);
+                Ok(pull_requests)
+            }
+            Self::Schedule(_) => list_open().await,
+        }
+    }
+}
+
+async fn download_pull_request(
+    octo: &Octocrab,
+    owner: &str,
+    repository: &Repository,
+    number: u64,
+) -> Result<PullRequest, ActionError> {
+    get_pull_request(octo, owner, &repository.name, number)
+        .await
+        .map_err(ActionError::github(format!(
+            "Downloading PR {}#{}",
+            repository.full_name, number
+        )))
+}
+
+async fn list_open_pull_requests(
+    octo: &Octocrab,
+    owner: &str,
+    repository: &Repository,
+    page_size: u8,
+    max_pages: u32,
+) -> Result<Vec<PullRequest>, ActionError> {
+    get_repo_pull_requests(octo, owner, &repository.name, page_size, max_pages, None)
+        .await
+        .map_err(ActionError::github(format!(
+            "Listing pull requests of {}",
+            repository.full_name
+        )))
+}
+
+impl WorkflowDispatchEvent {
+    /// Value of the `pr_number` input. Inputs are strings, unless they are declared with
+    /// the `number` type.
+    fn pr_number(&self) -> Result<Option<u64>, ActionError> {
+        let value = self
+            .inputs
+            .as_ref()
+            .and_then(|inputs| inputs.get(PR_NUMBER_INPUT));
+        let invalid =
+            || ActionError::Parse(format!("invalid {} input: {:?}", PR_NUMBER_INPUT, value));
+        match value {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(number)) if number.trim().is_empty() => Ok(None),
+            Some(serde_json::Value::String(number)) => {
+                number.trim().parse().map(Some).map_err(|_| invalid())
+            }
+            Some(serde_json::Value::Number(number)) => {
+                number.as_u64().map(Some).ok_or_else(invalid)
+            }
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+    use crate::github_pull_request::{PullRequestState, Repository};
+    use crate::test_utils::mock_octocrab;
+    use rstest::rstest;
+    use serde_json::json;
+    use std::fs::File;
+    use std::io::BufReader;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn pull_request_json(number: u64, branch: &str) -> serde_json::Value {
+        json!({
+            "url": format!("https://api.github.com/repos/umatbro/km-dep/pulls/{}", number),
+            "id": number,
+            "number": number,
+            "title": format!("[BACK-{}] PR", number),
+            "body": "",
+            "locked": false,
+            "maintainer_can_modify": false,
+            "head": {"ref": branch, "sha": "abc"},
+            "base": {"ref": "main", "sha": "def"},
+        })
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let file = File::open("src/test_resources/action_payload.json").unwrap();
+        let reader = BufReader::new(file);
+        let result = match Event::parse("pull_request", reader).unwrap() {
+            Event::PullRequest(e) => e,
+            other => panic!("Unexpected event {:?}", other),
+        };
+
+        assert_eq!(result.action, "edited");
+        assert_eq!(result.sender.unwrap().login, "umatbro");
+        assert_eq!(result.pull_request.number, 2);
+        assert_eq!(result.pull_request.body, "next trigger");
+        assert_eq!(result.pull_request.title, "update cargo");
+        assert_eq!(result.pull_request.state, PullRequestState::Open);
+        assert!(!result.pull_request.draft);
+        assert!(result.pull_request.merged_at.is_none());
+        assert_eq!(result.pull_request.head.ref_name, "test-pr");
+        assert_eq!(
+            result.pull_request.head.sha,
+            "2afd1a956d055f2bcdfc91847f3b06ce4fecdf7c"
+        );
+        assert_eq!(result.repository.name, "km-dep");
+        assert_eq!(result.repository.get_owner().unwrap(), "umatbro");
+        assert_eq!(result.repository.full_name, "umatbro/km-dep");
+    }
+
+    #[rstest]
+    #[case("issue_comment", json!({"issue": {"number": 5, "pull_request": {}}}), vec![5])]
+    #[case("issue_comment", json!({"issue": {"number": 5}}), vec![])]
+    #[case("workflow_dispatch", json!({"inputs": {"pr_number": "5"}}), vec![5])]
+    #[case("workflow_dispatch", json!({"inputs": {"pr_number": 5}}), vec![5])]
+    #[case("workflow_dispatch", json!({"inputs": {"pr_number": ""}}), vec![1, 5, 7])]
+    #[case("workflow_dispatch", json!({"inputs": null}), vec![1, 5, 7])]
+    #[case("push", json!({"ref": "refs/heads/feature"}), vec![5, 7])]
+    #[case("schedule", json!({"schedule": "0 * * * *"}), vec![1, 5, 7])]
+    #[case("release", json!({}), vec![])]
+    #[tokio::test]
+    async fn test_get_pull_requests(
+        #[case] event_name: &str,
+        #[case] mut payload: serde_json::Value,
+        #[case] expected_numbers: Vec<u64>,
+    ) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/umatbro/km-dep/pulls/5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pull_request_json(5, "feature")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/umatbro/km-dep/pulls"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                pull_request_json(1, "main"),
+                pull_request_json(5, "feature"),
+                pull_request_json(7, "feature"),
+            ])))
+            .mount(&server)
+            .await;
+        let octo = mock_octocrab(&server);
+        if event_name != "schedule" {
+            payload["repository"] = json!({"name": "km-dep", "full_name": "umatbro/km-dep"});
+        }
+        let event = Event::parse(event_name, payload.to_string().as_bytes()).unwrap();
+        let repository = event
+            .repository()
+            .cloned()
+            .unwrap_or_else(|| Repository::from_full_name("umatbro/km-dep").unwrap());
+
+        let pull_requests = event
+            .get_pull_requests(&octo, &repository, 100, 1)
+            .await
+            .unwrap();
+
+        let numbers: Vec<u64> = pull_requests.iter().map(|pr| pr.number).collect();
+        assert_eq!(numbers, expected_numbers);
+    }
+
+    #[rstest]
+    #[case("pull_request", json!({"action": "opened"}), None)]
+    #[case("pull_request", json!({"action": "synchronize", "sender": {"login": "umatbro"}}), None)]
+    #[case("pull_request", json!({"action": "edited", "changes": {"title": {"from": "Old"}}}), None)]
+    #[case("pull_request", json!({"action": "edited", "changes": {"base": {"ref": {"from": "dev"}}}}), None)]
+    #[case("pull_request", json!({"action": "edited", "changes": {"body": {"from": "Old"}}}), Some("Neither the title"))]
+    #[case("pull_request", json!({"action": "opened", "sender": {"login": "github-actions[bot]"}}), Some("The event was sent by github-actions[bot]."))]
+    #[case("issue_comment", json!({"issue": {"number": 5}, "sender": {"login": "github-actions[bot]"}}), Some("The event was sent by"))]
+    #[case("push", json!({"ref": "refs/heads/main", "sender": {"login": "umatbro"}}), None)]
+    fn test_skip_reason(
+        #[case] event_name: &str,
+        #[case] mut payload: serde_json::Value,
+        #[case] expected: Option<&str>,
+    ) {
+        payload["repository"] = json!({"name": "km-dep", "full_name": "umatbro/km-dep"});
+        if event_name == "pull_request" {
+            payload["pull_request"] = json!({"number": 1, "title": "[BACK-1] PR", "body": ""});
+        }
+        let event = Event::parse(event_name, payload.to_string().as_bytes()).unwrap();
+
+        let result = event.skip_reason(&[String::from("github-actions[bot]")]);
+
+        match expected {
+            Some(reason) => assert!(result.unwrap().starts_with(reason)),
+            None => assert!(result.is_none(), "{:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_event_without_body() {
+        let file = File::open("src/test_resources/action_payload_without_body.json").unwrap();
+        let result = match Event::parse("pull_request", BufReader::new(file)).unwrap() {
+            Event::PullRequest(e) => e,
+            other => panic!("Unexpected event {:?}", other),
+        };
+
+        assert_eq!(result.action, "opened");
+        assert_eq!(result.pull_request.number, 2);
+        assert_eq!(result.pull_request.body, "");
+    }
+
+    #[test]
+    fn test_skip_body_edit() {
+        let file = File::open("src/test_resources/action_payload.json").unwrap();
+        let event = Event::parse("pull_request", BufReader::new(file)).unwrap();
+
+        assert!(event.skip_reason(&[]).is_some());
+    }
+
+    #[rstest]
+    #[case(json!({"inputs": {"pr_number": "abc"}}))]
+    #[case(json!({"inputs": {"pr_number": -1}}))]
+    #[case(json!({"inputs": {"pr_number": true}}))]
+    fn test_invalid_pr_number_input(#[case] mut payload: serde_json::Value) {
+        payload["repository"] = json!({"name": "km-dep", "full_name": "umatbro/km-dep"});
+        let event = match Event::parse("workflow_dispatch", payload.to_string().as_bytes()) {
+            Ok(Event::WorkflowDispatch(e)) => e,
+            other => panic!("Unexpected event {:?}", other),
+        };
+
+        assert!(event
+            .pr_number()
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid event payload: invalid pr_number input"));
+    }
+}
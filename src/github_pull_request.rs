@@ -1,29 +1,280 @@
-use octocrab;
+use chrono::{DateTime, Utc};
+use octocrab::models::IssueState;
 use octocrab::Octocrab;
-use pest::Parser;
-use serde::Deserialize;
+use pest::error::ErrorVariant;
+use pest::{Parser, Position};
+use serde::{Deserialize, Deserializer};
 use std::collections::HashSet;
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug)]
-pub struct Event {
-    pub pull_request: PullRequest,
-    pub repository: Repository,
-}
-
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 pub struct PullRequest {
     pub number: u64,
+    /// GitHub sends `null` for PRs without a description, it's read as an empty body.
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub body: String,
     pub title: String,
     pub html_url: Option<String>,
+    #[serde(default)]
+    pub state: PullRequestState,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub head: Head,
+}
+
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Head {
+    /// Name of the branch.
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    /// Last commit of the branch.
+    #[serde(default)]
+    pub sha: String,
+}
+
+/// Part of the pull request ticket numbers are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketSource {
+    Title,
+    Branch,
+}
+
+impl FromStr for TicketSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "title" => Ok(Self::Title),
+            "branch" => Ok(Self::Branch),
+            other => Err(format!(
+                "Invalid ticket source: {}, expected title or branch",
+                other
+            )),
+        }
+    }
+}
+
+/// Format of the ticket numbers in the PR title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleFormat {
+    /// `[BACK-12][MD-3] msg`
+    Brackets,
+    /// `BACK-12: msg`
+    Colon,
+    /// `(BACK-12) msg`
+    Parens,
+    /// `feat(BACK-12): msg`
+    Conventional,
+}
+
+impl TitleFormat {
+    fn rule(&self) -> Rule {
+        match self {
+            Self::Brackets => Rule::pr_title,
+            Self::Colon => Rule::colon_title,
+            Self::Parens => Rule::parens_title,
+            Self::Conventional => Rule::conventional_title,
+        }
+    }
+}
+
+impl FromStr for TitleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "brackets" => Ok(Self::Brackets),
+            "colon" => Ok(Self::Colon),
+            "parens" => Ok(Self::Parens),
+            "conventional" => Ok(Self::Conventional),
+            other => Err(format!(
+                "Invalid title format: {}, expected brackets, colon, parens or conventional",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings of the ticket number lookup.
+#[derive(Debug, Clone)]
+pub struct TicketParser {
+    /// Sources are checked in order, ticket numbers from the first source that has any are used.
+    pub sources: Vec<TicketSource>,
+    /// Formats of the title are tried in order, the first matching one is used.
+    pub title_formats: Vec<TitleFormat>,
+    /// Jira project keys ticket numbers are accepted for. All keys are accepted when empty.
+    pub project_keys: HashSet<String>,
+    /// Accept GitHub issue numbers, e.g. `#12`. Set when a tracker is configured for them.
+    pub issue_numbers: bool,
+}
+
+impl Default for TicketParser {
+    fn default() -> Self {
+        Self {
+            sources: vec![TicketSource::Title],
+            title_formats: vec![TitleFormat::Brackets],
+            project_keys: HashSet::new(),
+            issue_numbers: false,
+        }
+    }
+}
+
+impl TicketParser {
+    /// Find ticket numbers in the PR title, trying all configured formats.
+    /// If none of them matches, the error of the first format is returned.
+    fn parse_title(&self, input: &str) -> Result<HashSet<String>, TicketError> {
+        let mut first_error = None;
+        for format in &self.title_formats {
+            match parse_title(input, format.rule()) {
+                Ok(ticket_numbers) => return self.filter_project_keys(ticket_numbers, input),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| no_ticket_error(Rule::pr_title, input)))
+    }
+
+    fn parse_branch_name(&self, input: &str) -> Result<HashSet<String>, TicketError> {
+        self.filter_project_keys(parse_branch_name(input)?, input)
+    }
+
+    /// Drop ticket numbers with project keys outside of the allowlist, so strings like `UTF-8`
+    /// are not taken for tickets, and GitHub issue numbers if they aren't accepted. An error is
+    /// returned if no ticket number is left.
+    fn filter_project_keys(
+        &self,
+        ticket_numbers: HashSet<String>,
+        input: &str,
+    ) -> Result<HashSet<String>, TicketError> {
+        let (allowed, rejected): (HashSet<String>, HashSet<String>) = ticket_numbers
+            .into_iter()
+            .partition(|ticket_number| match project_key(ticket_number) {
+                ISSUE_PROJECT_KEY => self.issue_numbers,
+                key => {
+                    self.project_keys.is_empty() || self.project_keys.contains(&key.to_uppercase())
+                }
+            });
+        if allowed.is_empty() && self.project_keys.is_empty() {
+            return Err(no_ticket_error(Rule::ticket_num, input));
+        }
+        if allowed.is_empty() {
+            let mut rejected: Vec<String> = rejected.into_iter().collect();
+            rejected.sort();
+            let mut project_keys: Vec<&String> = self.project_keys.iter().collect();
+            project_keys.sort();
+            return Err(Box::new(pest::error::Error::new_from_pos(
+                ErrorVariant::CustomError {
+                    message: format!(
+                        "{} not in the allowed projects ({})",
+                        rejected.join(", "),
+                        project_keys
+                            .into_iter()
+                            .map(|key| key.as_str())
+                            .collect::<Vec<&str>>()
+                            .join(", ")
+                    ),
+                },
+                Position::from_start(input),
+            )));
+        }
+        Ok(allowed)
+    }
+}
+
+/// Project key of the GitHub issue numbers, e.g. `#12`.
+pub const ISSUE_PROJECT_KEY: &str = "#";
+
+/// Project key of the `ticket_number`, e.g. `BACK` for `BACK-12`, `#` for the issue number `#12`.
+pub fn project_key(ticket_number: &str) -> &str {
+    if ticket_number.starts_with(ISSUE_PROJECT_KEY) {
+        return ISSUE_PROJECT_KEY;
+    }
+    ticket_number.rsplit_once('-').map_or("", |(key, _)| key)
+}
+
+/// The `state` field of a pull request. Merged pull requests are `Closed`.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PullRequestState {
+    #[default]
+    Open,
+    Closed,
+}
+
+/// State of a pull request as shown to the users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullRequestStatus {
+    Open,
+    Draft,
+    Merged,
+    Closed,
+}
+
+impl PullRequestStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Draft => "draft",
+            Self::Merged => "merged",
+            Self::Closed => "closed",
+        }
+    }
+
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Self::Open => "🟢 open",
+            Self::Draft => "📝 draft",
+            Self::Merged => "🟣 merged",
+            Self::Closed => "🔴 closed",
+        }
+    }
 }
 
 impl PullRequest {
-    /// Get ticket number from the PR title.
-    /// * PR title has to start with the ticket number.
-    /// * Ticket number has to be inside square brackets.
-    pub fn get_ticket_number(&self) -> Result<HashSet<String>, pest::error::Error<Rule>> {
-        parse_pr_title(&self.title)
+    /// Get ticket number from the PR title or branch name, checking the sources in the order
+    /// configured in `ticket_parser`.
+    /// * PR title has to start with the ticket number, in one of the configured `TitleFormat`s.
+    /// * Ticket number in the branch name can be anywhere, but has to be upper case.
+    /// * Only ticket numbers of the allowed project keys are returned.
+    ///
+    /// If no source contains a ticket number, the error of the first source is returned.
+    pub fn get_ticket_number(
+        &self,
+        ticket_parser: &TicketParser,
+    ) -> Result<HashSet<String>, TicketError> {
+        let mut first_error = None;
+        for source in &ticket_parser.sources {
+            let result = match source {
+                TicketSource::Title => ticket_parser.parse_title(&self.title),
+                TicketSource::Branch => ticket_parser.parse_branch_name(&self.head.ref_name),
+            };
+            match result {
+                Ok(ticket_numbers) => return Ok(ticket_numbers),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| no_ticket_error(Rule::pr_title, &self.title)))
+    }
+
+    pub fn status(&self) -> PullRequestStatus {
+        match (self.state, self.merged_at, self.draft) {
+            (PullRequestState::Closed, Some(_), _) => PullRequestStatus::Merged,
+            (PullRequestState::Closed, None, _) => PullRequestStatus::Closed,
+            (PullRequestState::Open, _, true) => PullRequestStatus::Draft,
+            (PullRequestState::Open, _, false) => PullRequestStatus::Open,
+        }
     }
 }
 
@@ -33,20 +284,45 @@ impl From<octocrab::models::pulls::PullRequest> for PullRequest {
             number: v.number,
             body: v.body.unwrap_or("".into()),
             title: v.title.unwrap_or("".into()),
-            html_url: match v.html_url {
-                Some(url) => Some(url.to_string()),
-                None => None,
+            html_url: v.html_url.map(|url| url.to_string()),
+            state: match v.state {
+                Some(IssueState::Closed) => PullRequestState::Closed,
+                _ => PullRequestState::Open,
+            },
+            draft: v.draft.unwrap_or(false),
+            merged_at: v.merged_at,
+            updated_at: v.updated_at,
+            head: Head {
+                ref_name: v.head.ref_field,
+                sha: v.head.sha,
             },
         }
     }
 }
 
+/// Pull requests downloaded from one of the lib repositories.
+#[derive(Debug, Default)]
+pub struct LibRepoPullRequests {
+    /// `owner/name` of the lib repository.
+    pub repo_full_name: String,
+    pub pull_requests: Vec<PullRequest>,
+}
+
 #[derive(Parser)]
 #[grammar = "pr_title.pest"]
 struct PrTitleParser;
 
-fn parse_pr_title(input: &str) -> Result<HashSet<String>, pest::error::Error<Rule>> {
-    let parse_result = PrTitleParser::parse(Rule::pr_title, input)?;
+/// Ticket number parse error, boxed as the pest error is large.
+pub type TicketError = Box<pest::error::Error<Rule>>;
+
+#[cfg(test)]
+fn parse_pr_title(input: &str) -> Result<HashSet<String>, TicketError> {
+    parse_title(input, Rule::pr_title)
+}
+
+/// Find ticket numbers in the title, `rule` is one of the title formats.
+fn parse_title(input: &str, rule: Rule) -> Result<HashSet<String>, TicketError> {
+    let parse_result = PrTitleParser::parse(rule, input)?;
     Ok(parse_result
         .flatten()
         .filter_map(|pair| match pair.as_rule() {
@@ -56,13 +332,71 @@ fn parse_pr_title(input: &str) -> Result<HashSet<String>, pest::error::Error<Rul
         .collect())
 }
 
-#[derive(Deserialize, Debug)]
+/// Find all ticket numbers in the branch name.
+fn parse_branch_name(input: &str) -> Result<HashSet<String>, TicketError> {
+    let parse_result = PrTitleParser::parse(Rule::branch_name, input)?;
+    let ticket_numbers: HashSet<String> = parse_result
+        .flatten()
+        .filter_map(|pair| match pair.as_rule() {
+            Rule::branch_ticket_num => Some(String::from(pair.as_str())),
+            _ => None,
+        })
+        .collect();
+    if ticket_numbers.is_empty() {
+        return Err(no_ticket_error(Rule::branch_ticket_num, input));
+    }
+    Ok(ticket_numbers)
+}
+
+/// Describe the ticket number parse error for people, naming the expected ticket formats
+/// instead of the grammar rules.
+pub fn ticket_error_message(error: &pest::error::Error<Rule>) -> String {
+    error
+        .clone()
+        .renamed_rules(|rule| match rule {
+            Rule::ticket_num => String::from("ticket number, e.g. BACK-12"),
+            Rule::ticket_num_section => {
+                String::from("ticket number in square brackets, e.g. [BACK-12]")
+            }
+            Rule::branch_ticket_num => {
+                String::from("upper case ticket number in the branch name, e.g. feature/BACK-12")
+            }
+            other => format!("{:?}", other),
+        })
+        .to_string()
+}
+
+/// Error reported when the `input` was parsed, but contains no ticket number.
+fn no_ticket_error(expected: Rule, input: &str) -> TicketError {
+    Box::new(pest::error::Error::new_from_pos(
+        ErrorVariant::ParsingError {
+            positives: vec![expected],
+            negatives: vec![],
+        },
+        Position::from_start(input),
+    ))
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Repository {
     pub name: String,
     pub full_name: String,
 }
 
 impl Repository {
+    /// Repository from its `owner/name`, e.g. the `GITHUB_REPOSITORY` variable.
+    pub fn from_full_name(full_name: &str) -> Result<Self, String> {
+        match full_name.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(Self {
+                    name: name.to_string(),
+                    full_name: full_name.to_string(),
+                })
+            }
+            _ => Err(String::from(full_name)),
+        }
+    }
+
     pub fn get_owner(&self) -> Result<String, String> {
         let parts: Vec<&str> = self.full_name.split('/').collect();
         if parts.len() != 2 {
@@ -73,46 +407,157 @@ impl Repository {
     }
 }
 
-impl Event {
-    pub async fn set_pr_body(
-        &self,
-        octo: &Octocrab,
-        body: &String,
-    ) -> octocrab::Result<octocrab::models::pulls::PullRequest> {
-        let result = octo
-            .pulls(&self.repository.get_owner().unwrap(), &self.repository.name)
-            .update(self.pull_request.number as u64)
-            .body(body)
-            .send()
-            .await;
-        result
+/// Download the pull request `number` of the `owner/repo_name` repository.
+pub async fn get_pull_request(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+) -> octocrab::Result<PullRequest> {
+    Ok(octo.pulls(owner, repo_name).get(number).await?.into())
+}
+
+/// Replace the body of the pull request `number` in the `owner/repo_name` repository.
+pub async fn set_pull_request_body(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+    body: &String,
+) -> octocrab::Result<octocrab::models::pulls::PullRequest> {
+    octo.pulls(owner, repo_name)
+        .update(number)
+        .body(body)
+        .send()
+        .await
+}
+
+/// Result of `update_pull_request_body`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BodyUpdate {
+    Updated,
+    UpToDate,
+    /// The pull request kept changing while the body was computed, it was not updated.
+    Conflict,
+}
+
+/// Error of `update_pull_request_body`.
+#[derive(Debug)]
+pub enum BodyUpdateError<E> {
+    Github(octocrab::Error),
+    /// The body couldn't be computed.
+    Body(E),
+}
+
+impl<E> From<octocrab::Error> for BodyUpdateError<E> {
+    fn from(e: octocrab::Error) -> Self {
+        Self::Github(e)
     }
 }
 
+/// Set the body computed by `get_body` from the live pull request `number`.
+///
+/// GitHub has no conditional updates of pull requests, so the pull request is downloaded again
+/// right before the update. If it changed since the body was computed (e.g. someone edited the
+/// description), the body is computed again from the new data, at most `max_attempts` times,
+/// so the changes are never overwritten.
+///
+/// The pull request the body was last computed from is returned with the result.
+pub async fn update_pull_request_body<F, E>(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+    max_attempts: u32,
+    get_body: F,
+) -> Result<(BodyUpdate, PullRequest), BodyUpdateError<E>>
+where
+    F: Fn(&PullRequest) -> Result<String, E>,
+{
+    let mut pull_request = get_pull_request(octo, owner, repo_name, number).await?;
+    for attempt in 1..=max_attempts {
+        let body = get_body(&pull_request).map_err(BodyUpdateError::Body)?;
+        if body == pull_request.body {
+            return Ok((BodyUpdate::UpToDate, pull_request));
+        }
+        let live_pull_request = get_pull_request(octo, owner, repo_name, number).await?;
+        if live_pull_request.updated_at != pull_request.updated_at {
+            log::info!(
+                "PR {}/{}#{} changed while it was being updated (attempt {} of {}).",
+                owner,
+                repo_name,
+                number,
+                attempt,
+                max_attempts
+            );
+            pull_request = live_pull_request;
+            continue;
+        }
+        set_pull_request_body(octo, owner, repo_name, number, &body).await?;
+        return Ok((BodyUpdate::Updated, pull_request));
+    }
+    Ok((BodyUpdate::Conflict, pull_request))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Event;
     use super::Rule;
-    use crate::github_pull_request::parse_pr_title;
+    use super::{
+        Head, PullRequest, PullRequestState, PullRequestStatus, Repository, TicketParser,
+        TicketSource, TitleFormat,
+    };
+    use crate::github_pull_request::{
+        parse_branch_name, parse_pr_title, project_key, ticket_error_message,
+        update_pull_request_body, BodyUpdate,
+    };
+    use crate::test_utils::mock_octocrab;
     use pest::error::ErrorVariant;
     use rstest::rstest;
-    use serde_json;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
     use std::collections::HashSet;
-    use std::fs::File;
-    use std::io::BufReader;
 
-    #[test]
-    fn test_parse_event() {
-        let file = File::open("src/test_resources/action_payload.json").unwrap();
-        let reader = BufReader::new(file);
-        let result: Event = serde_json::from_reader(reader).unwrap();
+    #[rstest]
+    #[case("umatbro/km-dep", Some(("umatbro", "km-dep")))]
+    #[case("km-dep", None)]
+    #[case("umatbro/", None)]
+    #[case("a/b/c", None)]
+    fn test_repository_from_full_name(
+        #[case] full_name: &str,
+        #[case] expected: Option<(&str, &str)>,
+    ) {
+        let result = Repository::from_full_name(full_name);
+        match expected {
+            Some((owner, name)) => {
+                let repository = result.unwrap();
+                assert_eq!(repository.get_owner().unwrap(), owner);
+                assert_eq!(repository.name, name);
+                assert_eq!(repository.full_name, full_name);
+            }
+            None => assert!(result.is_err()),
+        }
+    }
 
-        assert_eq!(result.pull_request.number, 2);
-        assert_eq!(result.pull_request.body, "next trigger");
-        assert_eq!(result.pull_request.title, "update cargo");
-        assert_eq!(result.repository.name, "km-dep");
-        assert_eq!(result.repository.get_owner().unwrap(), "umatbro");
-        assert_eq!(result.repository.full_name, "umatbro/km-dep");
+    #[rstest]
+    #[case(PullRequestState::Open, false, false, PullRequestStatus::Open)]
+    #[case(PullRequestState::Open, true, false, PullRequestStatus::Draft)]
+    #[case(PullRequestState::Closed, false, true, PullRequestStatus::Merged)]
+    #[case(PullRequestState::Closed, true, false, PullRequestStatus::Closed)]
+    fn test_pull_request_status(
+        #[case] state: PullRequestState,
+        #[case] draft: bool,
+        #[case] merged: bool,
+        #[case] expected_status: PullRequestStatus,
+    ) {
+        let pull_request = PullRequest {
+            state,
+            draft,
+            merged_at: merged.then(chrono::Utc::now),
+            ..Default::default()
+        };
+        assert_eq!(pull_request.status(), expected_status);
     }
 
     #[rstest]
@@ -125,6 +570,180 @@ mod tests {
         assert_eq!(expected_result, result.unwrap());
     }
 
+    #[rstest]
+    #[case("feature/BACK-123-fix-typo", vec!["BACK-123"])]
+    #[case("BACK-1", vec!["BACK-1"])]
+    #[case("BACK-1_MD-22-two-tickets", vec!["BACK-1", "MD-22"])]
+    #[case("release/BACK-12345678901234567890123", vec![])]
+    #[case("fix-2-lower-case", vec![])]
+    #[case("UTF-8x", vec!["UTF-8"])]
+    #[case("main", vec![])]
+    fn test_parse_branch_name(#[case] branch: &str, #[case] expected_ticket_nums: Vec<&str>) {
+        let result = parse_branch_name(branch);
+        if expected_ticket_nums.is_empty() {
+            assert!(result.is_err());
+            return;
+        }
+        let expected_result =
+            HashSet::from_iter(expected_ticket_nums.into_iter().map(|v| v.to_string()));
+        assert_eq!(expected_result, result.unwrap());
+    }
+
+    #[rstest]
+    #[case(vec![TicketSource::Title], "[BACK-1] Title", "BACK-2-branch", Some("BACK-1"))]
+    #[case(vec![TicketSource::Branch], "[BACK-1] Title", "BACK-2-branch", Some("BACK-2"))]
+    #[case(vec![TicketSource::Title, TicketSource::Branch], "[BACK-1] Title", "BACK-2-branch", Some("BACK-1"))]
+    #[case(vec![TicketSource::Branch, TicketSource::Title], "[BACK-1] Title", "BACK-2-branch", Some("BACK-2"))]
+    #[case(vec![TicketSource::Title, TicketSource::Branch], "fix typo", "feature/BACK-123-fix-typo", Some("BACK-123"))]
+    #[case(vec![TicketSource::Branch, TicketSource::Title], "[BACK-1] Title", "main", Some("BACK-1"))]
+    #[case(vec![TicketSource::Title], "fix typo", "feature/BACK-123-fix-typo", None)]
+    #[case(vec![TicketSource::Title, TicketSource::Branch], "fix typo", "main", None)]
+    fn test_get_ticket_number_sources(
+        #[case] sources: Vec<TicketSource>,
+        #[case] title: &str,
+        #[case] branch: &str,
+        #[case] expected_ticket_num: Option<&str>,
+    ) {
+        let pull_request = PullRequest {
+            title: title.to_string(),
+            head: Head {
+                ref_name: branch.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = pull_request.get_ticket_number(&TicketParser {
+            sources,
+            ..Default::default()
+        });
+        match expected_ticket_num {
+            Some(ticket_num) => {
+                assert_eq!(result.unwrap(), HashSet::from([ticket_num.to_string()]))
+            }
+            None => assert!(result.is_err()),
+        }
+    }
+
+    #[rstest]
+    #[case("[BACK-12] msg", vec!["BACK-12"])]
+    #[case("BACK-12: msg", vec!["BACK-12"])]
+    #[case("BACK-12, MD-3: msg", vec!["BACK-12", "MD-3"])]
+    #[case("(BACK-12) msg", vec!["BACK-12"])]
+    #[case("(BACK-12)(MD-3) msg", vec!["BACK-12", "MD-3"])]
+    #[case("feat(BACK-12): msg", vec!["BACK-12"])]
+    #[case("fix(BACK-12,MD-3)!: msg", vec!["BACK-12", "MD-3"])]
+    #[case("feat: msg", vec![])]
+    #[case("UTF-8: fix encoding", vec![])]
+    #[case("Fix UTF-8 in [BACK-1]", vec![])]
+    fn test_get_ticket_number_title_formats(
+        #[case] title: &str,
+        #[case] expected_ticket_nums: Vec<&str>,
+    ) {
+        let ticket_parser = TicketParser {
+            sources: vec![TicketSource::Title],
+            title_formats: vec![
+                TitleFormat::Brackets,
+                TitleFormat::Colon,
+                TitleFormat::Parens,
+                TitleFormat::Conventional,
+            ],
+            project_keys: HashSet::from([String::from("BACK"), String::from("MD")]),
+            issue_numbers: false,
+        };
+        let pull_request = PullRequest {
+            title: title.to_string(),
+            ..Default::default()
+        };
+
+        let result = pull_request.get_ticket_number(&ticket_parser);
+
+        if expected_ticket_nums.is_empty() {
+            assert!(result.is_err());
+            return;
+        }
+        let expected_result =
+            HashSet::from_iter(expected_ticket_nums.into_iter().map(|v| v.to_string()));
+        assert_eq!(expected_result, result.unwrap());
+    }
+
+    #[rstest]
+    #[case("[BACK-1][UTF-8] Title", "main", Some(vec!["BACK-1"]))]
+    #[case("[UTF-8] Title", "main", None)]
+    #[case("[UTF-8] Title", "UTF-8-BACK-2", Some(vec!["BACK-2"]))]
+    #[case("[back-3] Lower case key", "main", Some(vec!["back-3"]))]
+    fn test_get_ticket_number_project_keys(
+        #[case] title: &str,
+        #[case] branch: &str,
+        #[case] expected_ticket_nums: Option<Vec<&str>>,
+    ) {
+        let ticket_parser = TicketParser {
+            sources: vec![TicketSource::Title, TicketSource::Branch],
+            project_keys: HashSet::from([String::from("BACK")]),
+            ..Default::default()
+        };
+        let pull_request = PullRequest {
+            title: title.to_string(),
+            head: Head {
+                ref_name: branch.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let result = pull_request.get_ticket_number(&ticket_parser);
+
+        match expected_ticket_nums {
+            Some(ticket_nums) => assert_eq!(
+                result.unwrap(),
+                HashSet::from_iter(ticket_nums.into_iter().map(|v| v.to_string()))
+            ),
+            None => assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains("UTF-8 not in the allowed projects (BACK)")),
+        }
+    }
+
+    #[rstest]
+    #[case("[#12] Title", true, Some(vec!["#12"]))]
+    #[case("[#12][BACK-1] Title", true, Some(vec!["#12", "BACK-1"]))]
+    #[case("[#12][BACK-1] Title", false, Some(vec!["BACK-1"]))]
+    #[case("[#12] Title", false, None)]
+    #[case("[#] Title", true, None)]
+    fn test_get_ticket_number_issue_numbers(
+        #[case] title: &str,
+        #[case] issue_numbers: bool,
+        #[case] expected_ticket_nums: Option<Vec<&str>>,
+    ) {
+        let ticket_parser = TicketParser {
+            sources: vec![TicketSource::Title],
+            issue_numbers,
+            ..Default::default()
+        };
+        let pull_request = PullRequest {
+            title: title.to_string(),
+            ..Default::default()
+        };
+
+        let result = pull_request.get_ticket_number(&ticket_parser);
+
+        match expected_ticket_nums {
+            Some(ticket_nums) => assert_eq!(
+                result.unwrap(),
+                HashSet::from_iter(ticket_nums.into_iter().map(|v| v.to_string()))
+            ),
+            None => assert!(result.is_err()),
+        }
+    }
+
+    #[rstest]
+    #[case("BACK-12", "BACK")]
+    #[case("#12", "#")]
+    #[case("12", "")]
+    fn test_project_key(#[case] ticket_number: &str, #[case] expected: &str) {
+        assert_eq!(project_key(ticket_number), expected);
+    }
+
     #[rstest]
     #[case("No ticket number", Rule::ticket_num_section)]
     #[case("", Rule::ticket_num_section)]
@@ -152,4 +771,107 @@ mod tests {
             _ => panic!("The error variant is incorrect."),
         }
     }
+
+    #[rstest]
+    #[case(
+        "No ticket number",
+        "expected ticket number in square brackets, e.g. [BACK-12]"
+    )]
+    #[case("[99] Wrong ticket num format", "expected ticket number, e.g. BACK-12")]
+    fn test_ticket_error_message(#[case] pr_title: &str, #[case] expected: &str) {
+        let error = parse_pr_title(pr_title).unwrap_err();
+
+        let message = ticket_error_message(&error);
+
+        assert!(message.contains(pr_title), "{}", message);
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    /// Mock the pull request `versions` (`updated_at`, `body`), each served once, in order.
+    /// The last one is served for all the following requests.
+    async fn mock_pull_request_versions(server: &MockServer, versions: &[(&str, &str)]) {
+        for (i, (updated_at, body)) in versions.iter().enumerate() {
+            let mock = Mock::given(method("GET"))
+                .and(path("/repos/org/app/pulls/1"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "url": "https://api.github.com/repos/org/app/pulls/1",
+                    "id": 1,
+                    "number": 1,
+                    "title": "[BACK-1] PR",
+                    "body": body,
+                    "updated_at": updated_at,
+                    "head": {"ref": "feature", "sha": "abc"},
+                    "base": {"ref": "main", "sha": "def"},
+                })))
+                .with_priority(i as u8 + 1);
+            let mock = match i + 1 < versions.len() {
+                true => mock.up_to_n_times(1),
+                false => mock,
+            };
+            mock.mount(server).await;
+        }
+    }
+
+    #[rstest]
+    #[case(&[("2023-01-01T00:00:00Z", "Text")], Some("Text\nupdate"), BodyUpdate::Updated, "Text")]
+    #[case(&[("2023-01-01T00:00:00Z", "Text\nupdate")], None, BodyUpdate::UpToDate, "Text\nupdate")]
+    #[case(
+        &[
+            ("2023-01-01T00:00:00Z", "Text"),
+            ("2023-01-01T00:01:00Z", "Edited text"),
+            ("2023-01-01T00:01:00Z", "Edited text"),
+        ],
+        Some("Edited text\nupdate"),
+        BodyUpdate::Updated,
+        "Edited text"
+    )]
+    #[case(
+        &[
+            ("2023-01-01T00:00:00Z", "Text"),
+            ("2023-01-01T00:01:00Z", "Edited text"),
+            ("2023-01-01T00:02:00Z", "Edited text again"),
+        ],
+        None,
+        BodyUpdate::Conflict,
+        "Edited text again"
+    )]
+    #[tokio::test]
+    async fn test_update_pull_request_body(
+        #[case] versions: &[(&str, &str)],
+        #[case] expected_body: Option<&str>,
+        #[case] expected_result: BodyUpdate,
+        #[case] expected_source_body: &str,
+    ) {
+        let server = MockServer::start().await;
+        mock_pull_request_versions(&server, versions).await;
+        let patch = Mock::given(method("PATCH")).and(path("/repos/org/app/pulls/1"));
+        match expected_body {
+            Some(body) => patch
+                .and(body_partial_json(json!({ "body": body })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "url": "https://api.github.com/repos/org/app/pulls/1",
+                    "id": 1,
+                    "number": 1,
+                    "head": {"ref": "feature", "sha": "abc"},
+                    "base": {"ref": "main", "sha": "def"},
+                })))
+                .expect(1),
+            None => patch.respond_with(ResponseTemplate::new(500)).expect(0),
+        }
+        .mount(&server)
+        .await;
+        let octo = mock_octocrab(&server);
+
+        let result = update_pull_request_body(&octo, "org", "app", 1, 2, |pr| {
+            Ok::<String, ()>(match pr.body.ends_with("update") {
+                true => pr.body.clone(),
+                false => format!("{}\nupdate", pr.body),
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result.0, expected_result);
+        assert_eq!(result.1.body, expected_source_body);
+    }
 }
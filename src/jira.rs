@@ -0,0 +1,1119 @@
+//! Jira REST API client, used to show the details of the tickets next to their numbers, to
+//! move the issues through their workflow when the PR changes and to link the PRs from them.
+//!
+//! <https://developer.atlassian.com/cloud/jira/platform/rest/v2/api-group-issues/#api-rest-api-2-issue-issueidorkey-get>
+//! <https://developer.atlassian.com/cloud/jira/platform/rest/v2/api-group-issues/#api-rest-api-2-issue-issueidorkey-transitions-get>
+//! <https://developer.atlassian.com/cloud/jira/platform/rest/v2/api-group-issue-remote-links/>
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+
+use crate::cli::JiraLink;
+use crate::github_pull_request::{
+    project_key, LibRepoPullRequests, PullRequest, PullRequestStatus, TicketParser,
+};
+use crate::secret::Secret;
+use crate::tracker::{Tracker, TrackerIssue};
+
+/// Jira requests are not retried, a slow Jira only makes the action skip the details.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Email and API token of the Jira user the action authenticates as.
+/// <https://support.atlassian.com/atlassian-account/docs/manage-api-tokens-for-your-atlassian-account/>
+#[derive(Debug, Clone)]
+pub struct JiraCredentials {
+    pub email: String,
+    pub api_token: Secret,
+}
+
+#[derive(Deserialize)]
+struct IssueResponse {
+    fields: IssueFields,
+}
+
+#[derive(Deserialize)]
+struct IssueFields {
+    summary: String,
+    status: Status,
+    issuetype: Named,
+    assignee: Option<User>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    name: String,
+    status_category: Option<StatusCategory>,
+}
+
+/// One of `new`, `indeterminate` or `done`, the statuses of the workflows are customizable.
+#[derive(Deserialize)]
+struct StatusCategory {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct TransitionsResponse {
+    transitions: Vec<Transition>,
+}
+
+/// Transition available from the current status of an issue.
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+    /// Status the transition leads to.
+    to: Named,
+}
+
+/// PR events the Jira issues of its tickets can be transitioned on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Opened,
+    Reopened,
+    ReadyForReview,
+    ConvertedToDraft,
+    Merged,
+    /// Closed without merging.
+    Closed,
+}
+
+impl LifecycleEvent {
+    /// Event of the `pull_request` event `action`, `None` for actions that don't move the
+    /// tickets, e.g. `edited`.
+    pub fn from_action(action: &str, merged: bool) -> Option<Self> {
+        match action {
+            "opened" => Some(Self::Opened),
+            "reopened" => Some(Self::Reopened),
+            "ready_for_review" => Some(Self::ReadyForReview),
+            "converted_to_draft" => Some(Self::ConvertedToDraft),
+            "closed" if merged => Some(Self::Merged),
+            "closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Opened => "opened",
+            Self::Reopened => "reopened",
+            Self::ReadyForReview => "ready_for_review",
+            Self::ConvertedToDraft => "converted_to_draft",
+            Self::Merged => "merged",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+impl FromStr for LifecycleEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "opened" => Ok(Self::Opened),
+            "reopened" => Ok(Self::Reopened),
+            "ready_for_review" => Ok(Self::ReadyForReview),
+            "converted_to_draft" => Ok(Self::ConvertedToDraft),
+            "merged" => Ok(Self::Merged),
+            "closed" => Ok(Self::Closed),
+            other => Err(format!(
+                "Invalid PR event: {}, expected opened, reopened, ready_for_review, \
+converted_to_draft, merged or closed",
+                other
+            )),
+        }
+    }
+}
+
+/// Transition of the Jira issues on a PR event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionRule {
+    /// Only issues of this project are transitioned, `None` applies to all projects.
+    pub project_key: Option<String>,
+    pub event: LifecycleEvent,
+    /// Name of the transition, or of the status it leads to, e.g. `Done`.
+    pub transition: String,
+}
+
+impl FromStr for TransitionRule {
+    type Err = String;
+
+    /// Parse `event=Transition` or `PROJECT:event=Transition`, e.g. `OPS:merged=Deployed`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid Jira transition: {}, expected event=Transition or PROJECT:event=Transition",
+                s.trim()
+            )
+        };
+        let (event, transition) = s.split_once('=').ok_or_else(invalid)?;
+        let (project_key, event) = match event.split_once(':') {
+            Some((project_key, event)) => (Some(project_key.trim().to_uppercase()), event),
+            None => (None, event),
+        };
+        let transition = transition.trim();
+        if transition.is_empty() || project_key.as_ref().is_some_and(|key| key.is_empty()) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            project_key,
+            event: event.parse()?,
+            transition: transition.to_string(),
+        })
+    }
+}
+
+/// Transitions of the Jira issues on PR events, configured per project.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct JiraTransitions(pub Vec<TransitionRule>);
+
+impl JiraTransitions {
+    /// Parse rules separated by commas or new lines.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        input
+            .split([',', '\n'])
+            .filter(|v| !v.trim().is_empty())
+            .map(TransitionRule::from_str)
+            .collect::<Result<Vec<TransitionRule>, String>>()
+            .map(Self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Transition of the `ticket` on the `event`. Rules of its project take precedence over the
+    /// ones for all projects.
+    pub fn transition(&self, ticket: &str, event: LifecycleEvent) -> Option<&str> {
+        let project_key = project_key(ticket);
+        let rules = || self.0.iter().filter(|rule| rule.event == event);
+        rules()
+            .find(|rule| {
+                rule.project_key
+                    .as_deref()
+                    .is_some_and(|key| key.eq_ignore_ascii_case(project_key))
+            })
+            .or_else(|| rules().find(|rule| rule.project_key.is_none()))
+            .map(|rule| rule.transition.as_str())
+    }
+}
+
+/// Result of transitioning an issue.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IssueTransition {
+    /// The issue was moved to the status.
+    Transitioned(String),
+    /// The issue already is in the target status.
+    UpToDate,
+    /// The workflow doesn't allow the transition from the current status.
+    Unavailable { status: String },
+    /// There is no such issue, or the user can't see it.
+    NotFound,
+}
+
+/// Link from a Jira issue to a PR, shown in the `Web links` of the issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteLink {
+    /// URL of the PR, also identifies the link, so it's updated instead of added again.
+    pub url: String,
+    /// `owner/name#number: PR title`.
+    pub title: String,
+    /// `open`, `draft`, `merged` or `closed`.
+    pub summary: String,
+    /// The PR is merged or closed, Jira shows the link struck through.
+    pub resolved: bool,
+}
+
+impl RemoteLink {
+    /// Link to the `pull_request` of the repository `repo_full_name`, `None` if it has no URL.
+    pub fn pull_request(repo_full_name: &str, pull_request: &PullRequest) -> Option<Self> {
+        let status = pull_request.status();
+        Some(Self {
+            url: pull_request.html_url.clone()?,
+            title: format!(
+                "{}#{}: {}",
+                repo_full_name, pull_request.number, pull_request.title
+            ),
+            summary: status.name().to_string(),
+            resolved: matches!(
+                status,
+                PullRequestStatus::Merged | PullRequestStatus::Closed
+            ),
+        })
+    }
+}
+
+/// Links from the Jira issues of the `pull_request`, by ticket. Every issue links the PR, lib PRs
+/// are linked only from the issues of the tickets they share with it.
+pub fn remote_links_by_ticket(
+    repo_full_name: &str,
+    pull_request: &PullRequest,
+    lib_repo_pulls: &[LibRepoPullRequests],
+    ticket_parser: &TicketParser,
+) -> BTreeMap<String, Vec<RemoteLink>> {
+    let Ok(tickets) = pull_request.get_ticket_number(ticket_parser) else {
+        return BTreeMap::new();
+    };
+    let link = RemoteLink::pull_request(repo_full_name, pull_request);
+    let mut links: BTreeMap<String, Vec<RemoteLink>> = tickets
+        .iter()
+        .map(|ticket| (ticket.clone(), link.iter().cloned().collect()))
+        .collect();
+    for lib_repo in lib_repo_pulls {
+        for lib_pull_request in &lib_repo.pull_requests {
+            let Ok(lib_tickets) = lib_pull_request.get_ticket_number(ticket_parser) else {
+                continue;
+            };
+            let Some(lib_link) =
+                RemoteLink::pull_request(&lib_repo.repo_full_name, lib_pull_request)
+            else {
+                continue;
+            };
+            for ticket in tickets.intersection(&lib_tickets) {
+                links
+                    .entry(ticket.clone())
+                    .or_default()
+                    .push(lib_link.clone());
+            }
+        }
+    }
+    links
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteLinkResponse {
+    global_id: Option<String>,
+    object: RemoteLinkObject,
+}
+
+#[derive(Deserialize)]
+struct RemoteLinkObject {
+    url: String,
+    title: String,
+    summary: Option<String>,
+    status: Option<RemoteLinkStatus>,
+}
+
+#[derive(Deserialize)]
+struct RemoteLinkStatus {
+    resolved: Option<bool>,
+}
+
+impl From<RemoteLinkResponse> for RemoteLink {
+    fn from(v: RemoteLinkResponse) -> Self {
+        Self {
+            url: v.object.url,
+            title: v.object.title,
+            summary: v.object.summary.unwrap_or_default(),
+            resolved: v
+                .object
+                .status
+                .and_then(|status| status.resolved)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// What was done with the remote link of an issue.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RemoteLinkUpdate {
+    Created,
+    Updated,
+    UpToDate,
+    /// There is no such issue, or the user can't see it.
+    NotFound,
+}
+
+impl From<IssueResponse> for TrackerIssue {
+    fn from(v: IssueResponse) -> Self {
+        Self {
+            summary: v.fields.summary,
+            // The status belongs to the `Done` category.
+            closed: v
+                .fields
+                .status
+                .status_category
+                .is_some_and(|category| category.key == "done"),
+            status: v.fields.status.name,
+            issue_type: v.fields.issuetype.name,
+            assignee: v.fields.assignee.map(|user| user.display_name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JiraClient {
+    api_url: Url,
+    credentials: JiraCredentials,
+    http: Client,
+}
+
+impl JiraClient {
+    pub fn new(jira_link: &JiraLink, credentials: JiraCredentials) -> reqwest::Result<Self> {
+        Ok(Self {
+            api_url: jira_link.api_url(),
+            credentials,
+            http: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+        })
+    }
+
+    /// Download the issue `key`, e.g. `BACK-12`. `None` if there is no such issue, or the user
+    /// can't see it.
+    pub async fn get_issue(&self, key: &str) -> reqwest::Result<Option<TrackerIssue>> {
+        let response = self
+            .http
+            .get(self.api_url.join("issue/").unwrap().join(key).unwrap())
+            .query(&[("fields", "summary,status,issuetype,assignee")])
+            .basic_auth(
+                &self.credentials.email,
+                Some(self.credentials.api_token.expose()),
+            )
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let issue: IssueResponse = response.error_for_status()?.json().await?;
+        Ok(Some(issue.into()))
+    }
+
+    /// Move the issue `key` with the `transition`, given by its name or by the name of the status
+    /// it leads to. Issues already in the target status are left alone, so the transition can be
+    /// repeated safely. The dry run only checks the transition is possible.
+    pub async fn transition_issue(
+        &self,
+        key: &str,
+        transition: &str,
+        dry_run: bool,
+    ) -> reqwest::Result<IssueTransition> {
+        let Some(issue) = self.get_issue(key).await? else {
+            return Ok(IssueTransition::NotFound);
+        };
+        if issue.status.eq_ignore_ascii_case(transition) {
+            return Ok(IssueTransition::UpToDate);
+        }
+        let url = self.transitions_url(key);
+        let response: TransitionsResponse = self
+            .http
+            .get(url.clone())
+            .basic_auth(
+                &self.credentials.email,
+                Some(self.credentials.api_token.expose()),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let Some(found) = response.transitions.into_iter().find(|t| {
+            t.name.eq_ignore_ascii_case(transition) || t.to.name.eq_ignore_ascii_case(transition)
+        }) else {
+            return Ok(IssueTransition::Unavailable {
+                status: issue.status,
+            });
+        };
+        if found.to.name.eq_ignore_ascii_case(&issue.status) {
+            return Ok(IssueTransition::UpToDate);
+        }
+        if !dry_run {
+            self.http
+                .post(url)
+                .basic_auth(
+                    &self.credentials.email,
+                    Some(self.credentials.api_token.expose()),
+                )
+                .json(&json!({"transition": {"id": found.id}}))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(IssueTransition::Transitioned(found.to.name))
+    }
+
+    /// Move the issues of the `tickets` as the `transitions` configure for the `event`. Failures
+    /// are only reported, tickets are moved by hand when Jira is down.
+    pub async fn transition_issues(
+        &self,
+        tickets: &[String],
+        transitions: &JiraTransitions,
+        event: LifecycleEvent,
+        dry_run: bool,
+    ) {
+        let prefix = if dry_run { "Dry run: " } else { "" };
+        for key in tickets {
+            let Some(transition) = transitions.transition(key, event) else {
+                continue;
+            };
+            match self.transition_issue(key, transition, dry_run).await {
+                Ok(IssueTransition::Transitioned(status)) => {
+                    log::info!("{prefix}Jira issue {key} moved to {status}.")
+                }
+                Ok(IssueTransition::UpToDate) => {
+                    log::info!("Jira issue {key} is already {transition}.")
+                }
+                Ok(IssueTransition::Unavailable { status }) => log::warn!(
+                    "Jira issue {key} can't be moved from {status} with the {transition} transition."
+                ),
+                Ok(IssueTransition::NotFound) => log::warn!("Jira issue {key} not found."),
+                Err(e) => log::warn!("Couldn't move Jira issue {key} to {transition}: {e}"),
+            }
+        }
+    }
+
+    /// Add the `link` to the issue `key`, or update the link to the same URL. Links that didn't
+    /// change are not written again, and the dry run only reports what would be written.
+    pub async fn set_remote_link(
+        &self,
+        key: &str,
+        link: &RemoteLink,
+        dry_run: bool,
+    ) -> reqwest::Result<RemoteLinkUpdate> {
+        let url = self.remote_links_url(key);
+        let response = self
+            .http
+            .get(url.clone())
+            .basic_auth(
+                &self.credentials.email,
+                Some(self.credentials.api_token.expose()),
+            )
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(RemoteLinkUpdate::NotFound);
+        }
+        let links: Vec<RemoteLinkResponse> = response.error_for_status()?.json().await?;
+        let current_link = links
+            .into_iter()
+            .find(|l| l.global_id.as_deref() == Some(link.url.as_str()))
+            .map(RemoteLink::from);
+        let update = match current_link {
+            Some(current_link) if current_link == *link => return Ok(RemoteLinkUpdate::UpToDate),
+            Some(_) => RemoteLinkUpdate::Updated,
+            None => RemoteLinkUpdate::Created,
+        };
+        if !dry_run {
+            // Jira updates the link with the same global id instead of adding another one.
+            self.http
+                .post(url)
+                .basic_auth(
+                    &self.credentials.email,
+                    Some(self.credentials.api_token.expose()),
+                )
+                .json(&json!({
+                    "globalId": link.url,
+                    "application": {"type": "com.github", "name": "GitHub"},
+                    "object": {
+                        "url": link.url,
+                        "title": link.title,
+                        "summary": link.summary,
+                        "icon": {"url16x16": "https://github.com/favicon.ico", "title": "GitHub"},
+                        "status": {"resolved": link.resolved},
+                    },
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(update)
+    }
+
+    /// Add the `links` to the issue of the `ticket`. Failures are only reported, the links are
+    /// written again on the next run.
+    pub async fn set_remote_links(&self, ticket: &str, links: &[RemoteLink], dry_run: bool) {
+        let prefix = if dry_run { "Dry run: " } else { "" };
+        for link in links {
+            match self.set_remote_link(ticket, link, dry_run).await {
+                Ok(RemoteLinkUpdate::Created) => {
+                    log::info!("{prefix}Link to {} added to Jira issue {ticket}.", link.url)
+                }
+                Ok(RemoteLinkUpdate::Updated) => {
+                    log::info!(
+                        "{prefix}Link to {} updated in Jira issue {ticket}.",
+                        link.url
+                    )
+                }
+                Ok(RemoteLinkUpdate::UpToDate) => {
+                    log::info!("Link to {} in Jira issue {ticket} is up to date.", link.url)
+                }
+                Ok(RemoteLinkUpdate::NotFound) => {
+                    log::warn!("Jira issue {ticket} not found.");
+                    return;
+                }
+                Err(e) => log::warn!("Couldn't link {} from Jira issue {ticket}: {e}", link.url),
+            }
+        }
+    }
+
+    fn remote_links_url(&self, key: &str) -> Url {
+        self.api_url
+            .join("issue/")
+            .unwrap()
+            .join(&format!("{}/remotelink", key))
+            .unwrap()
+    }
+
+    fn transitions_url(&self, key: &str) -> Url {
+        self.api_url
+            .join("issue/")
+            .unwrap()
+            .join(&format!("{}/transitions", key))
+            .unwrap()
+    }
+
+    /// Download the issues of the ticket numbers `keys`, `None` for tickets Jira doesn't know.
+    /// Tickets that couldn't be downloaded are left out and shown without the details. If Jira
+    /// can't be reached, the rest isn't tried.
+    pub async fn get_issues(&self, keys: &[String]) -> HashMap<String, Option<TrackerIssue>> {
+        let mut issues = HashMap::new();
+        for key in keys {
+            match self.get_issue(key).await {
+                Ok(issue) => {
+                    if issue.is_none() {
+                        log::warn!("Jira issue {key} not found.");
+                    }
+                    issues.insert(key.clone(), issue);
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    log::warn!("Jira can't be reached, tickets are shown without details: {e}");
+                    break;
+                }
+                Err(e) => log::warn!("Couldn't download Jira issue {key}: {e}"),
+            }
+        }
+        issues
+    }
+}
+
+/// Jira the tickets link to, with the issue lookup if the credentials are set.
+#[derive(Debug)]
+pub struct JiraTracker {
+    link: JiraLink,
+    client: Option<JiraClient>,
+}
+
+impl JiraTracker {
+    pub fn new(link: JiraLink, credentials: Option<&JiraCredentials>) -> Self {
+        let client = credentials.and_then(|credentials| {
+            JiraClient::new(&link, credentials.clone())
+                .map_err(|e| log::warn!("Couldn't create the Jira client, Jira is skipped: {e}"))
+                .ok()
+        });
+        Self { link, client }
+    }
+}
+
+#[async_trait]
+impl Tracker for JiraTracker {
+    fn ticket_url(&self, ticket: &str) -> String {
+        self.link.ticket_url(ticket)
+    }
+
+    async fn get_issues(&self, tickets: &[String]) -> HashMap<String, Option<TrackerIssue>> {
+        match &self.client {
+            Some(client) => client.get_issues(tickets).await,
+            None => HashMap::new(),
+        }
+    }
+
+    fn jira_client(&self) -> Option<&JiraClient> {
+        self.client.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        remote_links_by_ticket, IssueTransition, JiraClient, JiraCredentials, JiraTransitions,
+        LifecycleEvent, RemoteLink, RemoteLinkUpdate, TransitionRule,
+    };
+    use crate::cli::JiraLink;
+    use crate::github_pull_request::{
+        LibRepoPullRequests, PullRequest, PullRequestState, TicketParser,
+    };
+    use crate::secret::Secret;
+    use crate::tracker::{TicketDetails, TrackerIssue};
+    use rstest::rstest;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn jira_client(uri: &str) -> JiraClient {
+        JiraClient::new(
+            &JiraLink::parse(uri).unwrap(),
+            JiraCredentials {
+                email: String::from("bot@example.com"),
+                api_token: Secret::new(String::from("token")),
+            },
+        )
+        .unwrap()
+    }
+
+    async fn mock_issue(server: &MockServer, key: &str, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(format!("/rest/api/2/issue/{}", key)))
+            .and(query_param("fields", "summary,status,issuetype,assignee"))
+            // bot@example.com:token
+            .and(header(
+                "Authorization",
+                "Basic Ym90QGV4YW1wbGUuY29tOnRva2Vu",
+            ))
+            .respond_with(response)
+            .mount(server)
+            .await;
+    }
+
+    fn issue_json(summary: &str, assignee: Option<&str>) -> serde_json::Value {
+        json!({
+            "key": "ignored",
+            "fields": {
+                "summary": summary,
+                "status": {"name": "In Review", "id": "3", "statusCategory": {"key": "indeterminate"}},
+                "issuetype": {"name": "Bug"},
+                "assignee": assignee.map(|name| json!({"displayName": name})),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get_issues() {
+        let server = MockServer::start().await;
+        mock_issue(
+            &server,
+            "BACK-42",
+            ResponseTemplate::new(200)
+                .set_body_json(issue_json("Fix login timeout", Some("alice"))),
+        )
+        .await;
+        mock_issue(
+            &server,
+            "BACK-43",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "fields": {
+                    "summary": "Unassigned",
+                    "status": {"name": "Done", "statusCategory": {"key": "done"}},
+                    "issuetype": {"name": "Task"},
+                    "assignee": null,
+                }
+            })),
+        )
+        .await;
+        mock_issue(&server, "BACK-44", ResponseTemplate::new(404)).await;
+        mock_issue(&server, "BACK-45", ResponseTemplate::new(500)).await;
+        let client = jira_client(&server.uri());
+
+        let issues = client
+            .get_issues(&["BACK-42", "BACK-43", "BACK-44", "BACK-45"].map(String::from))
+            .await;
+
+        assert_eq!(
+            issues,
+            HashMap::from([
+                (
+                    String::from("BACK-42"),
+                    Some(TrackerIssue {
+                        summary: String::from("Fix login timeout"),
+                        status: String::from("In Review"),
+                        issue_type: String::from("Bug"),
+                        assignee: Some(String::from("alice")),
+                        closed: false,
+                    })
+                ),
+                (
+                    String::from("BACK-43"),
+                    Some(TrackerIssue {
+                        summary: String::from("Unassigned"),
+                        status: String::from("Done"),
+                        issue_type: String::from("Task"),
+                        assignee: None,
+                        closed: true,
+                    })
+                ),
+                (String::from("BACK-44"), None),
+            ])
+        );
+
+        let ticket_details = TicketDetails {
+            trackers: None,
+            issues,
+        };
+        let problems: Vec<Option<&str>> = ["BACK-42", "BACK-43", "BACK-44", "BACK-45"]
+            .iter()
+            .map(|ticket| ticket_details.ticket_problem(ticket))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![None, Some("closed"), Some("not found"), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_issues_unreachable() {
+        // Nothing listens on the port 1, the connection is refused.
+        let client = jira_client("http://127.0.0.1:1");
+
+        let issues = client
+            .get_issues(&["BACK-1", "BACK-2"].map(String::from))
+            .await;
+
+        assert!(issues.is_empty());
+    }
+
+    #[rstest]
+    #[case("opened", false, Some(LifecycleEvent::Opened))]
+    #[case("ready_for_review", false, Some(LifecycleEvent::ReadyForReview))]
+    #[case("closed", true, Some(LifecycleEvent::Merged))]
+    #[case("closed", false, Some(LifecycleEvent::Closed))]
+    #[case("edited", false, None)]
+    #[case("synchronize", false, None)]
+    fn test_lifecycle_event_from_action(
+        #[case] action: &str,
+        #[case] merged: bool,
+        #[case] expected: Option<LifecycleEvent>,
+    ) {
+        assert_eq!(LifecycleEvent::from_action(action, merged), expected);
+    }
+
+    #[rstest]
+    #[case("merged=Done", Ok((None, LifecycleEvent::Merged, "Done")))]
+    #[case(" opened = In Review ", Ok((None, LifecycleEvent::Opened, "In Review")))]
+    #[case("ops:merged=Deployed", Ok((Some("OPS"), LifecycleEvent::Merged, "Deployed")))]
+    #[case("merged", Err("Invalid Jira transition: merged"))]
+    #[case("merged=", Err("Invalid Jira transition: merged="))]
+    #[case(":merged=Done", Err("Invalid Jira transition: :merged=Done"))]
+    #[case("approved=Done", Err("Invalid PR event: approved"))]
+    fn test_parse_transition_rule(
+        #[case] input: &str,
+        #[case] expected: Result<(Option<&str>, LifecycleEvent, &str), &str>,
+    ) {
+        let result = input.parse::<TransitionRule>();
+        match expected {
+            Ok((project_key, event, transition)) => assert_eq!(
+                result.unwrap(),
+                TransitionRule {
+                    project_key: project_key.map(String::from),
+                    event,
+                    transition: transition.to_string(),
+                }
+            ),
+            Err(message) => {
+                let error = result.unwrap_err();
+                assert!(error.contains(message), "{}", error);
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("BACK-1", LifecycleEvent::Opened, Some("In Review"))]
+    #[case("BACK-1", LifecycleEvent::Merged, Some("Done"))]
+    #[case("OPS-1", LifecycleEvent::Merged, Some("Deployed"))]
+    #[case("ops-1", LifecycleEvent::Merged, Some("Deployed"))]
+    #[case("OPS-1", LifecycleEvent::Opened, Some("In Review"))]
+    #[case("BACK-1", LifecycleEvent::Closed, None)]
+    fn test_jira_transitions(
+        #[case] ticket: &str,
+        #[case] event: LifecycleEvent,
+        #[case] expected: Option<&str>,
+    ) {
+        // The project rule is found even if it comes after the rule for all projects.
+        let transitions =
+            JiraTransitions::parse("opened=In Review, merged=Done\nOPS:merged=Deployed\n").unwrap();
+
+        assert_eq!(transitions.transition(ticket, event), expected);
+    }
+
+    async fn mock_transitions(server: &MockServer, key: &str, transitions: &[(&str, &str, &str)]) {
+        let transitions: Vec<serde_json::Value> = transitions
+            .iter()
+            .map(|(id, name, to)| json!({"id": id, "name": name, "to": {"name": to}}))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path(format!("/rest/api/2/issue/{}/transitions", key)))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "transitions": transitions })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[rstest]
+    // By the name of the transition or of the status it leads to.
+    #[case(
+        "Merge",
+        false,
+        Ok(IssueTransition::Transitioned(String::from("Done"))),
+        1
+    )]
+    #[case(
+        "done",
+        false,
+        Ok(IssueTransition::Transitioned(String::from("Done"))),
+        1
+    )]
+    #[case(
+        "Done",
+        true,
+        Ok(IssueTransition::Transitioned(String::from("Done"))),
+        0
+    )]
+    // The issue mocked by issue_json is In Review.
+    #[case("In Review", false, Ok(IssueTransition::UpToDate), 0)]
+    #[case("Review again", false, Ok(IssueTransition::UpToDate), 0)]
+    #[case("Deployed", false, Ok(IssueTransition::Unavailable { status: String::from("In Review") }), 0)]
+    #[tokio::test]
+    async fn test_transition_issue(
+        #[case] transition: &str,
+        #[case] dry_run: bool,
+        #[case] expected: Result<IssueTransition, ()>,
+        #[case] expected_posts: u64,
+    ) {
+        let server = MockServer::start().await;
+        mock_issue(
+            &server,
+            "BACK-42",
+            ResponseTemplate::new(200).set_body_json(issue_json("Fix login timeout", None)),
+        )
+        .await;
+        mock_transitions(
+            &server,
+            "BACK-42",
+            &[
+                ("11", "Back to progress", "In Progress"),
+                ("21", "Merge", "Done"),
+                ("31", "Review again", "In Review"),
+            ],
+        )
+        .await;
+        Mock::given(method("POST"))
+            .and(path("/rest/api/2/issue/BACK-42/transitions"))
+            .and(body_json(json!({"transition": {"id": "21"}})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(expected_posts)
+            .mount(&server)
+            .await;
+        let client = jira_client(&server.uri());
+
+        let result = client
+            .transition_issue("BACK-42", transition, dry_run)
+            .await;
+
+        assert_eq!(result.map_err(|_| ()), expected);
+    }
+
+    #[tokio::test]
+    async fn test_transition_issue_not_found() {
+        let server = MockServer::start().await;
+        mock_issue(&server, "BACK-44", ResponseTemplate::new(404)).await;
+        let client = jira_client(&server.uri());
+
+        let result = client.transition_issue("BACK-44", "Done", false).await;
+
+        assert_eq!(result.unwrap(), IssueTransition::NotFound);
+    }
+
+    #[rstest]
+    #[case(PullRequestState::Open, false, "open", false)]
+    #[case(PullRequestState::Closed, true, "merged", true)]
+    #[case(PullRequestState::Closed, false, "closed", true)]
+    fn test_remote_link_pull_request(
+        #[case] state: PullRequestState,
+        #[case] merged: bool,
+        #[case] expected_summary: &str,
+        #[case] expected_resolved: bool,
+    ) {
+        let pull_request = PullRequest {
+            number: 7,
+            title: String::from("[BACK-1] Fix typo"),
+            html_url: Some(String::from("https://github.com/org/lib/pull/7")),
+            state,
+            merged_at: merged.then(chrono::Utc::now),
+            ..Default::default()
+        };
+
+        let link = RemoteLink::pull_request("org/lib", &pull_request).unwrap();
+
+        assert_eq!(
+            link,
+            RemoteLink {
+                url: String::from("https://github.com/org/lib/pull/7"),
+                title: String::from("org/lib#7: [BACK-1] Fix typo"),
+                summary: expected_summary.to_string(),
+                resolved: expected_resolved,
+            }
+        );
+        let without_url = PullRequest {
+            html_url: None,
+            ..pull_request
+        };
+        assert!(RemoteLink::pull_request("org/lib", &without_url).is_none());
+    }
+
+    #[test]
+    fn test_remote_links_by_ticket() {
+        let pull_request = |repo: &str, number: u64, title: &str| PullRequest {
+            number,
+            title: title.to_string(),
+            html_url: Some(format!("https://github.com/{}/pull/{}", repo, number)),
+            ..Default::default()
+        };
+        let main_pull_request = pull_request("org/app", 1, "[BACK-1][BACK-2] Fix");
+        let lib_repo_pulls = vec![LibRepoPullRequests {
+            repo_full_name: String::from("org/lib"),
+            pull_requests: vec![
+                pull_request("org/lib", 2, "[BACK-2] Lib fix"),
+                pull_request("org/lib", 3, "[BACK-1][BACK-2] Lib change"),
+                pull_request("org/lib", 4, "[BACK-3] Unrelated"),
+            ],
+        }];
+
+        let links = remote_links_by_ticket(
+            "org/app",
+            &main_pull_request,
+            &lib_repo_pulls,
+            &TicketParser::default(),
+        );
+
+        let urls: Vec<(&str, Vec<&str>)> = links
+            .iter()
+            .map(|(ticket, links)| {
+                let urls = links.iter().map(|link| link.url.as_str()).collect();
+                (ticket.as_str(), urls)
+            })
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                (
+                    "BACK-1",
+                    vec![
+                        "https://github.com/org/app/pull/1",
+                        "https://github.com/org/lib/pull/3"
+                    ]
+                ),
+                (
+                    "BACK-2",
+                    vec![
+                        "https://github.com/org/app/pull/1",
+                        "https://github.com/org/lib/pull/2",
+                        "https://github.com/org/lib/pull/3"
+                    ]
+                ),
+            ]
+        );
+    }
+
+    fn remote_link_json(url: &str, summary: &str, resolved: bool) -> serde_json::Value {
+        json!({
+            "id": 10000,
+            "globalId": url,
+            "object": {
+                "url": url,
+                "title": "org/app#1: [BACK-42] Fix",
+                "summary": summary,
+                "status": {"resolved": resolved},
+            }
+        })
+    }
+
+    #[rstest]
+    #[case(vec![], false, RemoteLinkUpdate::Created, 1)]
+    #[case(vec![], true, RemoteLinkUpdate::Created, 0)]
+    #[case(
+        vec![remote_link_json("https://github.com/org/app/pull/2", "open", false)],
+        false,
+        RemoteLinkUpdate::Created,
+        1
+    )]
+    #[case(
+        vec![remote_link_json("https://github.com/org/app/pull/1", "open", false)],
+        false,
+        RemoteLinkUpdate::Updated,
+        1
+    )]
+    #[case(
+        vec![remote_link_json("https://github.com/org/app/pull/1", "merged", true)],
+        false,
+        RemoteLinkUpdate::UpToDate,
+        0
+    )]
+    #[tokio::test]
+    async fn test_set_remote_link(
+        #[case] current_links: Vec<serde_json::Value>,
+        #[case] dry_run: bool,
+        #[case] expected: RemoteLinkUpdate,
+        #[case] expected_posts: u64,
+    ) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/issue/BACK-42/remotelink"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(current_links))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rest/api/2/issue/BACK-42/remotelink"))
+            .and(body_json(json!({
+                "globalId": "https://github.com/org/app/pull/1",
+                "application": {"type": "com.github", "name": "GitHub"},
+                "object": {
+                    "url": "https://github.com/org/app/pull/1",
+                    "title": "org/app#1: [BACK-42] Fix",
+                    "summary": "merged",
+                    "icon": {"url16x16": "https://github.com/favicon.ico", "title": "GitHub"},
+                    "status": {"resolved": true},
+                },
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(expected_posts)
+            .mount(&server)
+            .await;
+        let client = jira_client(&server.uri());
+        let link = RemoteLink {
+            url: String::from("https://github.com/org/app/pull/1"),
+            title: String::from("org/app#1: [BACK-42] Fix"),
+            summary: String::from("merged"),
+            resolved: true,
+        };
+
+        let result = client.set_remote_link("BACK-42", &link, dry_run).await;
+
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_set_remote_link_not_found() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/issue/BACK-44/remotelink"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let client = jira_client(&server.uri());
+        let link = RemoteLink {
+            url: String::from("https://github.com/org/app/pull/1"),
+            title: String::from("org/app#1: [BACK-44] Fix"),
+            summary: String::from("open"),
+            resolved: false,
+        };
+
+        let result = client.set_remote_link("BACK-44", &link, false).await;
+
+        assert_eq!(result.unwrap(), RemoteLinkUpdate::NotFound);
+    }
+}
@@ -0,0 +1,168 @@
+//! Leveled logging of the action.
+//!
+//! In the `github` format warnings and errors are printed as workflow commands, so they show up
+//! as annotations of the workflow run. Bulky data is logged in collapsible groups, see `group`.
+//!
+//! <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions>
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Plain messages, warnings and errors as `::warning::` and `::error::` annotations.
+    Github,
+    /// Messages prefixed with the level.
+    Text,
+    /// One JSON object per message.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "github" => Ok(Self::Github),
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "Invalid log format: {}, expected github, text or json",
+                other
+            )),
+        }
+    }
+}
+
+static LOGGER: Logger = Logger;
+static FORMAT: RwLock<LogFormat> = RwLock::new(LogFormat::Github);
+
+struct Logger;
+
+impl Log for Logger {
+    /// Only messages of the action are logged, the dependencies are too chatty on the debug level.
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!(
+                "{}",
+                format_message(
+                    format(),
+                    record.level(),
+                    record.target(),
+                    &record.args().to_string()
+                )
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger with the `info` level and the `github` format.
+/// Messages logged while the config is read use those defaults.
+pub fn init() {
+    log::set_logger(&LOGGER).expect("Logger is initialized only once");
+    log::set_max_level(LevelFilter::Info);
+}
+
+/// Apply the configured verbosity and format.
+pub fn configure(level: LevelFilter, log_format: LogFormat) {
+    log::set_max_level(level);
+    *FORMAT.write().unwrap() = log_format;
+}
+
+fn format() -> LogFormat {
+    *FORMAT.read().unwrap()
+}
+
+/// Log multiline `contents` under the `title`. In the `github` format the contents are wrapped
+/// in a `::group::`, which is collapsed in the workflow run log.
+pub fn group(level: Level, title: &str, contents: &str) {
+    if level > log::max_level() {
+        return;
+    }
+    match format() {
+        LogFormat::Github => {
+            println!("::group::{}", escape_data(title));
+            println!("{}", contents);
+            println!("::endgroup::");
+        }
+        LogFormat::Text | LogFormat::Json => log::log!(level, "{}:\n{}", title, contents),
+    }
+}
+
+fn format_message(log_format: LogFormat, level: Level, target: &str, message: &str) -> String {
+    match log_format {
+        LogFormat::Github => match level {
+            Level::Error => format!("::error::{}", escape_data(message)),
+            Level::Warn => format!("::warning::{}", escape_data(message)),
+            Level::Info | Level::Debug | Level::Trace => message.to_string(),
+        },
+        LogFormat::Text => format!("{:<5} {}", level, message),
+        LogFormat::Json => json!({
+            "level": level.as_str().to_lowercase(),
+            "target": target,
+            "message": message,
+        })
+        .to_string(),
+    }
+}
+
+/// Workflow command messages have to be on a single line.
+fn escape_data(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_message, LogFormat};
+    use log::Level;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(LogFormat::Github, Level::Info, "Done", "Done")]
+    #[case(LogFormat::Github, Level::Debug, "Done", "Done")]
+    #[case(LogFormat::Github, Level::Warn, "Stopped", "::warning::Stopped")]
+    #[case(
+        LogFormat::Github,
+        Level::Error,
+        "Failed\n100% broken",
+        "::error::Failed%0A100%25 broken"
+    )]
+    #[case(LogFormat::Text, Level::Info, "Done", "INFO  Done")]
+    #[case(LogFormat::Text, Level::Error, "Failed", "ERROR Failed")]
+    #[case(
+        LogFormat::Json,
+        Level::Warn,
+        "Stopped\nat 10",
+        r#"{"level":"warn","message":"Stopped\nat 10","target":"km::cli"}"#
+    )]
+    fn test_format_message(
+        #[case] log_format: LogFormat,
+        #[case] level: Level,
+        #[case] message: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            format_message(log_format, level, "km::cli", message),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("github", Ok(LogFormat::Github))]
+    #[case(" json", Ok(LogFormat::Json))]
+    #[case("yaml", Err(()))]
+    fn test_parse_log_format(#[case] input: &str, #[case] expected: Result<LogFormat, ()>) {
+        assert_eq!(input.parse::<LogFormat>().map_err(|_| ()), expected);
+    }
+}
@@ -1,64 +1,563 @@
 mod cli;
 mod common_lib_handler;
 mod description_manipulator;
+mod error;
+mod event;
 mod github_pull_request;
+mod jira;
+mod logger;
+mod secret;
+mod sticky_comment;
+mod summary_template;
+#[cfg(test)]
+mod test_utils;
+mod ticket_check;
+mod tracker;
 
 use crate::cli::read_cli_args;
 
+use chrono::{Duration, Utc};
+use log::Level;
+use octocrab::Octocrab;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
+use std::process::ExitCode;
 
-use crate::common_lib_handler::get_octocrab_instance_for_lib_repo;
-use crate::github_pull_request::{Event, PullRequest};
+use crate::cli::{Config, LibRepo, OutputMode};
+use crate::common_lib_handler::{get_octocrab_instance_for_lib_repo, get_repo_pull_requests};
+use crate::description_manipulator::get_related_pull_requests;
+use crate::error::ActionError;
+use crate::event::Event;
+use crate::github_pull_request::{
+    get_pull_request, update_pull_request_body, BodyUpdate, BodyUpdateError, LibRepoPullRequests,
+    PullRequest, Repository, TicketParser,
+};
+use crate::jira::{remote_links_by_ticket, LifecycleEvent};
+use crate::secret::Secret;
+use crate::sticky_comment::{find_sticky_comment, set_sticky_comment, CommentUpdate};
+use crate::ticket_check::{create_check_run, get_ticket_check};
+use crate::tracker::{TicketDetails, Trackers};
 
 #[macro_use]
 extern crate pest_derive;
 
+/// How many times a PR body update is retried when the PR changes in the meantime.
+const MAX_UPDATE_ATTEMPTS: u32 = 3;
+
 #[tokio::main]
-async fn main() {
-    let read_cli_result = read_cli_args();
-    let (app_id, private_key, lib_repo_name, jira_link) = match read_cli_result {
-        Ok(v) => {
-            println!("CLI arguments {:?}", &v);
-            v
+async fn main() -> ExitCode {
+    logger::init();
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if let ActionError::Config(message) = &e {
+                // The usage is printed as it is, only the first line is reported as the error.
+                if let Some((_, details)) = message.split_once('\n') {
+                    println!("{}", details);
+                }
+            }
+            log::error!("{}", e);
+            e.exit_code()
         }
-        Err(msg) => panic!("{msg}"),
-    };
-    let event = get_pr_details();
-    let github_token = get_github_token();
+    }
+}
+
+async fn run() -> Result<(), ActionError> {
+    let config = read_cli_args().map_err(ActionError::Config)?;
+    config.private_key.0.add_mask();
+    if let Some(jira_credentials) = &config.jira_credentials {
+        jira_credentials.api_token.add_mask();
+    }
+    logger::configure(config.log_level, config.log_format);
+    logger::group(Level::Info, "Config", &format!("{:#?}", &config));
+
+    let github_token = get_github_token()?;
+    github_token.add_mask();
+    let event = get_event()?;
     let octo = octocrab::OctocrabBuilder::new()
-        .personal_token(github_token)
+        .personal_token(github_token.expose().to_string())
         .build()
-        .unwrap();
+        .map_err(ActionError::github("Creating the GitHub client"))?;
 
-    let lib_repo_octo =
-        get_octocrab_instance_for_lib_repo(app_id, &private_key.0.as_bytes(), &lib_repo_name.0)
-            .await;
-    let lib_repo_octo = match lib_repo_octo {
-        Ok(oct) => oct,
-        Err(e) => panic!("There was an error authenticating lib repo: {:?}", e),
+    if let Event::Unsupported(event_name) = &event {
+        log::warn!("The {event_name} event is not supported, there is nothing to update.");
+        return Ok(());
+    }
+    if let Some(reason) = event.skip_reason(&config.ignored_senders) {
+        log::info!("Skipping the event. {reason}");
+        return Ok(());
+    }
+    let repository = match event.repository() {
+        Some(repository) => repository.clone(),
+        None => get_repository()?,
     };
+    let repo_owner = repository.get_owner().map_err(|full_name| {
+        ActionError::Parse(format!("invalid repository name: {}", full_name))
+    })?;
+    let pull_requests = event
+        .get_pull_requests(
+            &octo,
+            &repository,
+            config.lib_prs_page_size,
+            config.lib_prs_max_pages,
+        )
+        .await?;
+    if pull_requests.is_empty() {
+        log::info!("No pull requests to update.");
+        return Ok(());
+    }
 
-    let lib_repo_pulls: Vec<PullRequest> = lib_repo_octo
-        .pulls(event.repository.get_owner().unwrap(), &lib_repo_name.0)
-        .list()
-        .send()
+    let closed_lib_prs_since = match config.closed_lib_prs_days {
+        0 => None,
+        days => Some(Utc::now() - Duration::days(days.into())),
+    };
+    let mut lib_repo_pulls: Vec<LibRepoPullRequests> = Vec::with_capacity(config.lib_repos.len());
+    let mut lib_repo_octos: HashMap<String, Octocrab> = HashMap::new();
+    for (owner, repo_names) in group_lib_repos_by_owner(&config.lib_repos, &repo_owner) {
+        let Some(lib_repo_octo) = get_octocrab_instance_for_lib_repo(
+            config.app_id,
+            config.private_key.0.expose().as_bytes(),
+            &owner,
+            &repo_names,
+        )
         .await
-        .expect("There was an error downloading pull requests from lib repo.")
-        .take_items()
+        .map_err(|source| ActionError::Auth {
+            owner: owner.clone(),
+            source: Box::new(source),
+        })?
+        else {
+            continue;
+        };
+
+        for repo_name in &repo_names {
+            let pull_requests = get_repo_pull_requests(
+                &lib_repo_octo,
+                &owner,
+                repo_name,
+                config.lib_prs_page_size,
+                config.lib_prs_max_pages,
+                closed_lib_prs_since,
+            )
+            .await
+            .map_err(ActionError::github(format!(
+                "Listing pull requests of {owner}/{repo_name}"
+            )))?;
+            log::info!(
+                "Found {} pull requests in {owner}/{repo_name}.",
+                pull_requests.len()
+            );
+            lib_repo_pulls.push(LibRepoPullRequests {
+                repo_full_name: format!("{owner}/{repo_name}"),
+                pull_requests,
+            });
+        }
+        lib_repo_octos.insert(owner, lib_repo_octo);
+    }
+    logger::group(
+        Level::Debug,
+        "Pulls from lib repos",
+        &format!("{:#?}", lib_repo_pulls),
+    );
+
+    let trackers = Trackers::new(
+        &config.trackers,
+        config.jira_link.as_ref(),
+        config.jira_credentials.as_ref(),
+        &repository.full_name,
+    );
+    let ticket_details = get_ticket_details(&config, &trackers, &pull_requests).await;
+    for pull_request in &pull_requests {
+        update_pull_request(
+            &octo,
+            &config,
+            &repository,
+            &repo_owner,
+            pull_request,
+            &lib_repo_pulls,
+            &ticket_details,
+        )
+        .await?;
+    }
+
+    if config.link_lib_prs {
+        let mut main_repo_pulls = get_repo_pull_requests(
+            &octo,
+            &repo_owner,
+            &repository.name,
+            config.lib_prs_page_size,
+            config.lib_prs_max_pages,
+            None,
+        )
+        .await
+        .map_err(ActionError::github(format!(
+            "Listing pull requests of {}",
+            repository.full_name
+        )))?;
+        // The listing only contains open PRs, and the event may have more recent data of the updated ones.
+        main_repo_pulls.retain(|pr| !pull_requests.iter().any(|p| p.number == pr.number));
+        main_repo_pulls.extend(pull_requests.iter().cloned());
+
+        for lib_repo in &lib_repo_pulls {
+            let (owner, repo_name) = lib_repo.repo_full_name.split_once('/').unwrap();
+            let lib_repo_octo = &lib_repo_octos[owner];
+            let mut linked_numbers = HashSet::new();
+            let related_pull_requests = pull_requests.iter().flat_map(|pull_request| {
+                get_related_pull_requests(
+                    pull_request,
+                    &lib_repo.pull_requests,
+                    &config.ticket_parser,
+                )
+            });
+            for lib_pull_request in related_pull_requests {
+                // Lib PRs related to more updated PRs are linked once, with all of them.
+                if !linked_numbers.insert(lib_pull_request.number) {
+                    continue;
+                }
+                write_pull_request_body(
+                    lib_repo_octo,
+                    config.dry_run,
+                    owner,
+                    repo_name,
+                    lib_pull_request.number,
+                    |live_lib_pull_request| {
+                        Ok(description_manipulator::get_lib_update_body(
+                            live_lib_pull_request,
+                            &repository.full_name,
+                            &main_repo_pulls,
+                            &config.ticket_parser,
+                        ))
+                    },
+                )
+                .await?;
+            }
+        }
+    }
+
+    if let Event::PullRequest(e) = &event {
+        transition_jira_issues(&config, &trackers, &e.action, &e.pull_request).await;
+    }
+    if config.jira_remote_links {
+        link_jira_issues(
+            &config,
+            &trackers,
+            &repository,
+            &pull_requests,
+            &lib_repo_pulls,
+        )
+        .await;
+    }
+
+    if config.fail_on_invalid_tickets {
+        let invalid_tickets =
+            get_invalid_tickets(&pull_requests, &config.ticket_parser, &ticket_details);
+        if !invalid_tickets.is_empty() {
+            return Err(ActionError::InvalidTickets(invalid_tickets));
+        }
+    }
+    Ok(())
+}
+
+/// Update the body of the `pull_request` and report its ticket check. Both are computed from the
+/// live PR, the event payload may be outdated.
+async fn update_pull_request(
+    octo: &Octocrab,
+    config: &Config,
+    repository: &Repository,
+    repo_owner: &str,
+    pull_request: &PullRequest,
+    lib_repo_pulls: &[LibRepoPullRequests],
+    ticket_details: &TicketDetails<'_>,
+) -> Result<(), ActionError> {
+    let name = format!("{}#{}", repository.full_name, pull_request.number);
+    let live_pull_request = match config.output_mode {
+        OutputMode::Body => {
+            write_pull_request_body(
+                octo,
+                config.dry_run,
+                repo_owner,
+                &repository.name,
+                pull_request.number,
+                |live_pull_request| {
+                    description_manipulator::get_update_body(
+                        live_pull_request,
+                        repository,
+                        lib_repo_pulls,
+                        ticket_details,
+                        &config.ticket_parser,
+                        &config.template,
+                        &config.placement,
+                    )
+                    .map_err(ActionError::Template)
+                },
+            )
+            .await?
+        }
+        OutputMode::Comment => {
+            let live_pull_request =
+                get_pull_request(octo, repo_owner, &repository.name, pull_request.number)
+                    .await
+                    .map_err(ActionError::github(format!("Downloading PR {name}")))?;
+            let comment = description_manipulator::get_update_comment(
+                &live_pull_request,
+                repository,
+                lib_repo_pulls,
+                ticket_details,
+                &config.ticket_parser,
+                &config.template,
+            )
+            .map_err(ActionError::Template)?;
+            write_pull_request_comment(
+                octo,
+                config.dry_run,
+                repo_owner,
+                &repository.name,
+                pull_request.number,
+                comment.as_deref(),
+            )
+            .await?;
+            live_pull_request
+        }
+    };
+
+    if config.require_ticket {
+        let check = get_ticket_check(&live_pull_request, &config.ticket_parser);
+        if !config.dry_run {
+            create_check_run(octo, repo_owner, &repository.name, &check)
+                .await
+                .map_err(ActionError::github(format!(
+                    "Creating the ticket check run of {name}"
+                )))?;
+        }
+        log::info!("Ticket check of {name}: {:?}.", check.conclusion);
+    }
+    Ok(())
+}
+
+/// Links to the tickets of the `pull_requests`, with their issues from the trackers that have
+/// the lookup configured.
+async fn get_ticket_details<'a>(
+    config: &Config,
+    trackers: &'a Trackers,
+    pull_requests: &[PullRequest],
+) -> TicketDetails<'a> {
+    let mut tickets: Vec<String> = pull_requests
+        .iter()
+        .filter_map(|pr| pr.get_ticket_number(&config.ticket_parser).ok())
+        .flatten()
+        .collect::<HashSet<String>>()
         .into_iter()
-        .map(|v| v.into())
         .collect();
-    println!("Pulls from lib repo: {:#?}", lib_repo_pulls);
+    tickets.sort();
+    let ticket_details = TicketDetails {
+        trackers: Some(trackers),
+        issues: trackers.get_issues(&tickets).await,
+    };
+    logger::group(
+        Level::Debug,
+        "Ticket issues",
+        &format!("{:#?}", ticket_details.issues),
+    );
+    ticket_details
+}
+
+/// Move the Jira issues of the tickets of the `pull_request` as configured for the `action` of
+/// its `pull_request` event.
+async fn transition_jira_issues(
+    config: &Config,
+    trackers: &Trackers,
+    action: &str,
+    pull_request: &PullRequest,
+) {
+    if config.jira_transitions.is_empty() {
+        return;
+    }
+    let Some(event) = LifecycleEvent::from_action(action, pull_request.merged_at.is_some()) else {
+        log::debug!("No Jira transitions on the {action} action.");
+        return;
+    };
+    let Ok(tickets) = pull_request.get_ticket_number(&config.ticket_parser) else {
+        return;
+    };
+    let mut tickets: Vec<String> = tickets.into_iter().collect();
+    tickets.sort();
+    log::info!(
+        "Moving Jira issues of PR #{} on the {} event.",
+        pull_request.number,
+        event.name()
+    );
+    for (jira_client, tickets) in trackers.group_by_jira_client(&tickets) {
+        jira_client
+            .transition_issues(&tickets, &config.jira_transitions, event, config.dry_run)
+            .await;
+    }
+}
+
+/// Link every PR of the `pull_requests` from the Jira issues of its tickets, and the related lib
+/// PRs from the issues of the tickets they share with it.
+async fn link_jira_issues(
+    config: &Config,
+    trackers: &Trackers,
+    repository: &Repository,
+    pull_requests: &[PullRequest],
+    lib_repo_pulls: &[LibRepoPullRequests],
+) {
+    for pull_request in pull_requests {
+        let links = remote_links_by_ticket(
+            &repository.full_name,
+            pull_request,
+            lib_repo_pulls,
+            &config.ticket_parser,
+        );
+        let tickets: Vec<String> = links.keys().cloned().collect();
+        for (jira_client, tickets) in trackers.group_by_jira_client(&tickets) {
+            for ticket in tickets {
+                jira_client
+                    .set_remote_links(&ticket, &links[&ticket], config.dry_run)
+                    .await;
+            }
+        }
+    }
+}
+
+/// Tickets of the `pull_requests` Jira doesn't know or closed ones, with the reason.
+fn get_invalid_tickets(
+    pull_requests: &[PullRequest],
+    ticket_parser: &TicketParser,
+    ticket_details: &TicketDetails,
+) -> Vec<String> {
+    let tickets: BTreeSet<String> = pull_requests
+        .iter()
+        .filter_map(|pr| pr.get_ticket_number(ticket_parser).ok())
+        .flatten()
+        .collect();
+    tickets
+        .into_iter()
+        .filter_map(|ticket| {
+            let problem = ticket_details.ticket_problem(&ticket)?;
+            Some(format!("{ticket} ({problem})"))
+        })
+        .collect()
+}
+
+/// Update the body of the PR `number` with the body `get_body` computes from its live data,
+/// or only print the diff in the dry run mode. The live PR the body was computed from is returned.
+async fn write_pull_request_body<F>(
+    octo: &Octocrab,
+    dry_run: bool,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+    get_body: F,
+) -> Result<PullRequest, ActionError>
+where
+    F: Fn(&PullRequest) -> Result<String, ActionError>,
+{
+    let name = format!("{owner}/{repo_name}#{number}");
+    if dry_run {
+        let pull_request = get_pull_request(octo, owner, repo_name, number)
+            .await
+            .map_err(ActionError::github(format!("Downloading PR {name}")))?;
+        print_body_diff(&name, &pull_request.body, &get_body(&pull_request)?);
+        return Ok(pull_request);
+    }
+    let (update, pull_request) = update_pull_request_body(
+        octo,
+        owner,
+        repo_name,
+        number,
+        MAX_UPDATE_ATTEMPTS,
+        get_body,
+    )
+    .await
+    .map_err(|e| match e {
+        BodyUpdateError::Github(source) => {
+            ActionError::github(format!("Updating PR {name}"))(source)
+        }
+        BodyUpdateError::Body(e) => e,
+    })?;
+    match update {
+        BodyUpdate::Updated => log::info!("Success! PR {name} updated."),
+        BodyUpdate::UpToDate => log::info!("PR {name} is up to date."),
+        BodyUpdate::Conflict => return Err(ActionError::Conflict(name)),
+    }
+    Ok(pull_request)
+}
 
-    let body_to_set =
-        description_manipulator::get_update_body(&event.pull_request, &lib_repo_pulls, &jira_link);
-    let set_body_result = event
-        .set_pr_body(&octo, &body_to_set)
+/// Make the action comment of the PR `number` contain the `comment`, or delete it if there is
+/// no `comment`. Only the diff is printed in the dry run mode.
+async fn write_pull_request_comment(
+    octo: &Octocrab,
+    dry_run: bool,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+    comment: Option<&str>,
+) -> Result<(), ActionError> {
+    let name = format!("{owner}/{repo_name}#{number}");
+    if dry_run {
+        let current_comment = find_sticky_comment(octo, owner, repo_name, number)
+            .await
+            .map_err(ActionError::github(format!(
+                "Listing comments of PR {name}"
+            )))?
+            .and_then(|comment| comment.body);
+        match (current_comment, comment) {
+            (Some(_), None) => log::info!("Dry run: {name} comment would be deleted."),
+            (current_comment, Some(comment)) => print_body_diff(
+                &format!("{name} comment"),
+                current_comment.as_deref().unwrap_or_default(),
+                comment,
+            ),
+            (None, None) => log::info!("Dry run: {name} has nothing to comment."),
+        }
+        return Ok(());
+    }
+    let update = set_sticky_comment(octo, owner, repo_name, number, comment)
         .await
-        .expect("Error while updating PR");
-    println!("Success! PR {} updated.", set_body_result.number);
+        .map_err(ActionError::github(format!(
+            "Updating the comment of PR {name}"
+        )))?;
+    match update {
+        CommentUpdate::Created => log::info!("Success! Comment of PR {name} created."),
+        CommentUpdate::Updated => log::info!("Success! Comment of PR {name} updated."),
+        CommentUpdate::UpToDate => log::info!("Comment of PR {name} is up to date."),
+        CommentUpdate::Deleted => log::info!("Comment of PR {name} deleted, no ticket found."),
+        CommentUpdate::None => log::info!("PR {name} has nothing to comment."),
+    }
+    Ok(())
+}
+
+/// Print what would be changed in the body of the PR `name` in the dry run mode.
+fn print_body_diff(name: &str, body: &str, updated_body: &str) {
+    let diff = description_manipulator::get_body_diff(name, body, updated_body);
+    if diff.is_empty() {
+        log::info!("Dry run: {name} body is up to date.");
+    } else {
+        logger::group(
+            Level::Info,
+            &format!("Dry run: {name} body would be updated"),
+            &diff,
+        );
+    }
+}
+
+/// Group lib repositories by their owner, preserving the configured order.
+/// Repositories without an explicit owner belong to `default_owner`.
+fn group_lib_repos_by_owner(
+    lib_repos: &[LibRepo],
+    default_owner: &str,
+) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = vec![];
+    for lib_repo in lib_repos {
+        let owner = lib_repo.owner_or(default_owner);
+        match groups.iter_mut().find(|(o, _)| o == owner) {
+            Some((_, names)) => names.push(lib_repo.name.clone()),
+            None => groups.push((owner.to_string(), vec![lib_repo.name.clone()])),
+        }
+    }
+    groups
 }
 
 /// `GITHUB_EVENT_PATH`
@@ -67,23 +566,42 @@ async fn main() {
 /// For example, `/github/workflow/event.json`.
 ///
 /// <https://docs.github.com/en/actions/learn-github-actions/variables>
-fn get_pr_details() -> Event {
-    let event_path = env::var("GITHUB_EVENT_PATH");
-    let p = event_path.expect("GITHUB_EVENT_PATH not found.");
+fn get_event() -> Result<Event, ActionError> {
+    // Runs without the event name (e.g. local ones) are handled as pull request events.
+    let event_name = env::var("GITHUB_EVENT_NAME").unwrap_or_else(|_| String::from("pull_request"));
+    let p = env::var("GITHUB_EVENT_PATH")
+        .map_err(|_| ActionError::Config(String::from("Env GITHUB_EVENT_PATH not found.")))?;
 
-    let f = File::open(p).unwrap();
+    let f = File::open(&p).map_err(|source| ActionError::Io {
+        path: p.clone(),
+        source,
+    })?;
     let reader = BufReader::new(f);
-    let parsed = serde_json::from_reader(reader).unwrap();
+    let parsed =
+        Event::parse(&event_name, reader).map_err(|e| ActionError::Parse(e.to_string()))?;
 
-    println!("Data from GITHUB_EVENT_PATH: {:?}", &parsed);
-    parsed
+    logger::group(
+        Level::Debug,
+        "Data from GITHUB_EVENT_PATH",
+        &format!("{:#?}", &parsed),
+    );
+    Ok(parsed)
 }
 
-fn get_github_token() -> String {
-    let github_token = env::var("GITHUB_TOKEN").expect(
-        "Env GITHUB_TOKEN not found. Modify your config file to pass it to the action.\n\
-See example in https://github.com/marketplace/actions/github-api-request#usage",
-    );
-    println!("Github token: {}", github_token);
-    github_token
+/// `GITHUB_REPOSITORY`, for events without the repository in the payload.
+fn get_repository() -> Result<Repository, ActionError> {
+    let full_name = env::var("GITHUB_REPOSITORY")
+        .map_err(|_| ActionError::Config(String::from("Env GITHUB_REPOSITORY not found.")))?;
+    Repository::from_full_name(&full_name)
+        .map_err(|full_name| ActionError::Config(format!("Invalid GITHUB_REPOSITORY: {full_name}")))
+}
+
+fn get_github_token() -> Result<Secret, ActionError> {
+    let github_token = env::var("GITHUB_TOKEN").map_err(|_| {
+        ActionError::Config(String::from(
+            "Env GITHUB_TOKEN not found. Modify your config file to pass it to the action, \
+see example in https://github.com/marketplace/actions/github-api-request#usage",
+        ))
+    })?;
+    Ok(Secret::new(github_token))
 }
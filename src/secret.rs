@@ -0,0 +1,84 @@
+//! Secret values that must never show up in the action output.
+use std::fmt;
+
+const MASK: &str = "***";
+
+/// A token or a key. `Debug` and `Display` print a mask instead of the value,
+/// use `expose` where the value itself is needed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Workflow commands telling the runner to mask the value in the logs.
+    /// Multiline values (e.g. PEM keys) are registered line by line, the runner masks single lines only.
+    ///
+    /// <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#masking-a-value-in-a-log>
+    pub fn add_mask_commands(&self) -> Vec<String> {
+        self.0
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("::add-mask::{}", line))
+            .collect()
+    }
+
+    /// Register the value to be masked in the logs of the workflow run.
+    pub fn add_mask(&self) {
+        for command in self.add_mask_commands() {
+            println!("{}", command);
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", MASK)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Secret;
+
+    #[test]
+    fn test_secret_is_masked() {
+        let secret = Secret::new("ghs_token");
+
+        assert_eq!(format!("{}", secret), "***");
+        assert_eq!(format!("{:?}", secret), "Secret(***)");
+        assert_eq!(
+            format!("{:#?}", Some(&secret)),
+            "Some(\n    Secret(***),\n)"
+        );
+        assert_eq!(secret.expose(), "ghs_token");
+    }
+
+    #[test]
+    fn test_add_mask_commands() {
+        let secret = Secret::new("-----BEGIN KEY-----\nabc\n\n  def \n-----END KEY-----\n");
+
+        assert_eq!(
+            secret.add_mask_commands(),
+            vec![
+                "::add-mask::-----BEGIN KEY-----",
+                "::add-mask::abc",
+                "::add-mask::def",
+                "::add-mask::-----END KEY-----",
+            ]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! The action comment of a pull request, used instead of editing the PR description in the
+//! comment output mode. There is at most one such comment per PR, it is found by the action
+//! markers and updated in place.
+use octocrab::models::CommentId;
+use octocrab::{Octocrab, Page};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::description_manipulator::is_assigned_by_action;
+
+/// The fields of the issue comment the action needs.
+#[derive(Deserialize, Debug)]
+pub struct IssueComment {
+    pub id: u64,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub user: CommentAuthor,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommentAuthor {
+    #[serde(rename = "type")]
+    pub user_type: String,
+}
+
+impl IssueComment {
+    /// Comments are owned by the action if they are posted by a bot and contain the markers,
+    /// so the markers quoted by people are left alone.
+    fn is_owned_by_action(&self) -> bool {
+        self.user.user_type == "Bot" && self.body.as_deref().is_some_and(is_assigned_by_action)
+    }
+}
+
+/// Result of `set_sticky_comment`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommentUpdate {
+    Created,
+    Updated,
+    UpToDate,
+    Deleted,
+    /// There was no comment and there is nothing to say.
+    None,
+}
+
+/// Find the action comment of the pull request `number`.
+pub async fn find_sticky_comment(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+) -> octocrab::Result<Option<IssueComment>> {
+    let mut page: Page<IssueComment> = octo
+        .get(
+            format!("repos/{}/{}/issues/{}/comments", owner, repo_name, number),
+            Some(&json!({ "per_page": 100 })),
+        )
+        .await?;
+    loop {
+        if let Some(comment) = page
+            .take_items()
+            .into_iter()
+            .find(IssueComment::is_owned_by_action)
+        {
+            return Ok(Some(comment));
+        }
+        page = match octo.get_page(&page.next).await? {
+            Some(next_page) => next_page,
+            None => return Ok(None),
+        };
+    }
+}
+
+/// Make the action comment of the pull request `number` contain the `body`.
+/// The comment is created if it doesn't exist yet, and deleted if the `body` is `None`.
+pub async fn set_sticky_comment(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    number: u64,
+    body: Option<&str>,
+) -> octocrab::Result<CommentUpdate> {
+    let comment = find_sticky_comment(octo, owner, repo_name, number).await?;
+    match (comment, body) {
+        (None, None) => Ok(CommentUpdate::None),
+        (None, Some(body)) => {
+            let _: serde_json::Value = octo
+                .post(
+                    format!("repos/{}/{}/issues/{}/comments", owner, repo_name, number),
+                    Some(&json!({ "body": body })),
+                )
+                .await?;
+            Ok(CommentUpdate::Created)
+        }
+        (Some(comment), Some(body)) if comment.body.as_deref() == Some(body) => {
+            Ok(CommentUpdate::UpToDate)
+        }
+        (Some(comment), Some(body)) => {
+            let _: serde_json::Value = octo
+                .patch(
+                    format!(
+                        "repos/{}/{}/issues/comments/{}",
+                        owner, repo_name, comment.id
+                    ),
+                    Some(&json!({ "body": body })),
+                )
+                .await?;
+            Ok(CommentUpdate::Updated)
+        }
+        (Some(comment), None) => {
+            octo.issues(owner, repo_name)
+                .delete_comment(CommentId(comment.id))
+                .await?;
+            Ok(CommentUpdate::Deleted)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_sticky_comment, set_sticky_comment, CommentUpdate};
+    use crate::test_utils::mock_octocrab;
+    use octocrab::Octocrab;
+    use rstest::rstest;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ACTION_COMMENT: &str = "<!-- START KM-ACTION -->\nold\n<!-- END KM-ACTION -->\n";
+
+    fn comment_json(id: u64, user_type: &str, body: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "body": body,
+            "user": {"login": format!("user-{}", id), "type": user_type},
+        })
+    }
+
+    /// Mock two pages of the PR comments, the second one contains `comments`.
+    async fn mock_comments(comments: serde_json::Value) -> (MockServer, Octocrab) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/org/app/issues/1/comments"))
+            .and(query_param("per_page", "100"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([comment_json(1, "User", "LGTM")]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            "<{}/repos/org/app/issues/1/comments?page=2>; rel=\"next\"",
+                            server.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/org/app/issues/1/comments"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(comments))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let octo = mock_octocrab(&server);
+        (server, octo)
+    }
+
+    #[rstest]
+    #[case(json!([comment_json(2, "Bot", ACTION_COMMENT)]), Some(2))]
+    #[case(json!([comment_json(2, "User", ACTION_COMMENT), comment_json(3, "Bot", "Deployed")]), None)]
+    #[case(json!([]), None)]
+    #[tokio::test]
+    async fn test_find_sticky_comment(
+        #[case] comments: serde_json::Value,
+        #[case] expected_id: Option<u64>,
+    ) {
+        let (_server, octo) = mock_comments(comments).await;
+
+        let comment = find_sticky_comment(&octo, "org", "app", 1).await.unwrap();
+
+        assert_eq!(comment.map(|c| c.id), expected_id);
+    }
+
+    #[rstest]
+    #[case(
+        false,
+        Some("new"),
+        "POST",
+        "/repos/org/app/issues/1/comments",
+        CommentUpdate::Created
+    )]
+    #[case(
+        true,
+        Some("new"),
+        "PATCH",
+        "/repos/org/app/issues/comments/2",
+        CommentUpdate::Updated
+    )]
+    #[case(
+        true,
+        None,
+        "DELETE",
+        "/repos/org/app/issues/comments/2",
+        CommentUpdate::Deleted
+    )]
+    #[case(true, Some(ACTION_COMMENT), "-", "-", CommentUpdate::UpToDate)]
+    #[case(false, None, "-", "-", CommentUpdate::None)]
+    #[tokio::test]
+    async fn test_set_sticky_comment(
+        #[case] comment_exists: bool,
+        #[case] body: Option<&str>,
+        #[case] expected_method: &str,
+        #[case] expected_path: &str,
+        #[case] expected_result: CommentUpdate,
+    ) {
+        let comments = match comment_exists {
+            true => json!([comment_json(2, "Bot", ACTION_COMMENT)]),
+            false => json!([]),
+        };
+        let (server, octo) = mock_comments(comments).await;
+        for (mock_method, mock_path) in [
+            ("POST", "/repos/org/app/issues/1/comments"),
+            ("PATCH", "/repos/org/app/issues/comments/2"),
+            ("DELETE", "/repos/org/app/issues/comments/2"),
+        ] {
+            let expected_calls = match (mock_method, mock_path) == (expected_method, expected_path)
+            {
+                true => 1,
+                false => 0,
+            };
+            let mock = Mock::given(method(mock_method)).and(path(mock_path));
+            let mock = match (mock_method, body) {
+                ("DELETE", _) | (_, None) => mock,
+                (_, Some(body)) => mock.and(body_json(json!({ "body": body }))),
+            };
+            mock.respond_with(
+                ResponseTemplate::new(match mock_method {
+                    "DELETE" => 204,
+                    _ => 200,
+                })
+                .set_body_json(comment_json(2, "Bot", "new")),
+            )
+            .expect(expected_calls)
+            .mount(&server)
+            .await;
+        }
+
+        let result = set_sticky_comment(&octo, "org", "app", 1, body)
+            .await
+            .unwrap();
+
+        assert_eq!(result, expected_result);
+    }
+}
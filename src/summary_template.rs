@@ -0,0 +1,182 @@
+//! Template of the section the action adds to the PR description (or its comment).
+//!
+//! Templates use the minijinja syntax (a subset of Jinja2), see `DEFAULT_TEMPLATE` for the
+//! variables available in the `SummaryContext`.
+//! <https://docs.rs/minijinja/latest/minijinja/syntax/index.html>
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+use std::fmt;
+
+/// Reproduces the section the action added before templates were configurable.
+pub const DEFAULT_TEMPLATE: &str = include_str!("default_template.md.jinja");
+
+const TEMPLATE_NAME: &str = "summary";
+
+/// Compiled template of the summary section.
+pub struct SummaryTemplate {
+    env: Environment<'static>,
+    /// `None` for the default template.
+    path: Option<String>,
+}
+
+impl SummaryTemplate {
+    /// Compile the `source` of the template read from `path`. The template is rendered with
+    /// sample data right away, so mistakes are reported before anything is updated.
+    pub fn new(source: String, path: Option<String>) -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Chainable);
+        env.set_keep_trailing_newline(true);
+        env.add_template_owned(TEMPLATE_NAME, source)?;
+        let template = Self { env, path };
+        template.render(&SummaryContext::sample())?;
+        Ok(template)
+    }
+
+    /// Render the summary lines of the `context`.
+    pub fn render(&self, context: &SummaryContext) -> Result<Vec<String>, minijinja::Error> {
+        let rendered = self.env.get_template(TEMPLATE_NAME)?.render(context)?;
+        Ok(rendered.lines().map(String::from).collect())
+    }
+}
+
+impl Default for SummaryTemplate {
+    fn default() -> Self {
+        Self::new(DEFAULT_TEMPLATE.to_string(), None).expect("The default template is valid")
+    }
+}
+
+impl fmt::Debug for SummaryTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "SummaryTemplate({})", path),
+            None => write!(f, "SummaryTemplate(default)"),
+        }
+    }
+}
+
+/// Variables available in the summary template.
+#[derive(Serialize, Debug, Default)]
+pub struct SummaryContext {
+    pub repository: RepositoryContext,
+    pub pull_request: PullRequestContext,
+    /// Ticket numbers of the PR, sorted. Empty if none was found.
+    pub tickets: Vec<TicketContext>,
+    /// Why no ticket number was found, e.g. the expected title format.
+    pub ticket_error: Option<String>,
+    /// Lib repositories with PRs related to the PR, in the configured order.
+    pub lib_repos: Vec<LibRepoContext>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RepositoryContext {
+    pub full_name: String,
+    pub owner: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PullRequestContext {
+    pub number: u64,
+    pub title: String,
+    pub url: Option<String>,
+    pub branch: String,
+    /// `open`, `draft`, `merged` or `closed`.
+    pub status: String,
+    /// The status with an emoji, e.g. `🟢 open`.
+    pub marker: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct TicketContext {
+    pub number: String,
+    /// Link to the ticket, if the Jira host is configured.
+    pub url: Option<String>,
+    /// Jira doesn't know the ticket, e.g. there is a typo in the ticket number.
+    pub missing: bool,
+    /// The Jira issue is in one of the `Done` statuses.
+    pub closed: bool,
+    /// Details of the Jira issue, set if the Jira lookup is configured and the issue was found.
+    pub summary: Option<String>,
+    pub status: Option<String>,
+    pub issue_type: Option<String>,
+    pub assignee: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct LibRepoContext {
+    pub full_name: String,
+    pub pull_requests: Vec<PullRequestContext>,
+}
+
+impl SummaryContext {
+    /// Context with every variable set, used to check the templates.
+    fn sample() -> Self {
+        let pull_request = |number: u64, title: &str| PullRequestContext {
+            number,
+            title: title.to_string(),
+            url: Some(format!("https://github.com/org/app/pull/{}", number)),
+            branch: String::from("feature/BACK-1"),
+            status: String::from("open"),
+            marker: String::from("🟢 open"),
+        };
+        Self {
+            repository: RepositoryContext {
+                full_name: String::from("org/app"),
+                owner: String::from("org"),
+                name: String::from("app"),
+            },
+            pull_request: pull_request(1, "[BACK-1] Sample"),
+            tickets: vec![TicketContext {
+                number: String::from("BACK-1"),
+                url: Some(String::from("https://jira.example.com/browse/BACK-1")),
+                missing: false,
+                closed: false,
+                summary: Some(String::from("Sample ticket")),
+                status: Some(String::from("In Review")),
+                issue_type: Some(String::from("Bug")),
+                assignee: Some(String::from("alice")),
+            }],
+            ticket_error: None,
+            lib_repos: vec![LibRepoContext {
+                full_name: String::from("org/lib"),
+                pull_requests: vec![pull_request(2, "[BACK-1] Sample lib PR")],
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SummaryContext, SummaryTemplate, TicketContext};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("{% for ticket in tickets %}{{ ticket.number }}{% endfor %}", Ok(vec!["BACK-1"]))]
+    #[case(
+        "| Ticket |\n|---|\n{% for t in tickets %}| {{ t.number }} |\n{% endfor %}",
+        Ok(vec!["| Ticket |", "|---|", "| BACK-1 |"])
+    )]
+    #[case("{{ pull_request.missing.field }}", Ok(vec![]))]
+    #[case("{% for ticket in tickets %}", Err("syntax error"))]
+    #[case("{{ tickets | no_such_filter }}", Err("unknown filter"))]
+    fn test_summary_template(#[case] source: &str, #[case] expected: Result<Vec<&str>, &str>) {
+        let context = SummaryContext {
+            tickets: vec![TicketContext {
+                number: String::from("BACK-1"),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let result =
+            SummaryTemplate::new(source.to_string(), None).and_then(|t| t.render(&context));
+
+        match expected {
+            Ok(lines) => assert_eq!(result.unwrap(), lines),
+            Err(message) => {
+                let error = result.unwrap_err().to_string();
+                assert!(error.contains(message), "{}", error);
+            }
+        }
+    }
+}
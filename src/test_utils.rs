@@ -0,0 +1,12 @@
+//! Helpers shared by the tests of multiple modules.
+use octocrab::{Octocrab, OctocrabBuilder};
+use wiremock::MockServer;
+
+/// Octocrab client sending its requests to the mock `server`.
+pub fn mock_octocrab(server: &MockServer) -> Octocrab {
+    OctocrabBuilder::new()
+        .base_url(server.uri())
+        .unwrap()
+        .build()
+        .unwrap()
+}
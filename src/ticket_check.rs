@@ -0,0 +1,157 @@
+//! Check run reporting whether the pull request has a ticket number,
+//! so branch protection can block merging PRs without one.
+use octocrab::Octocrab;
+use serde::Serialize;
+
+use crate::github_pull_request::{ticket_error_message, PullRequest, TicketParser};
+
+/// Name of the check run, as shown in the PR checks and branch protection settings.
+pub const CHECK_NAME: &str = "km-action / ticket number";
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckConclusion {
+    Success,
+    Failure,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CheckOutput {
+    pub title: String,
+    pub summary: String,
+}
+
+/// Body of the create check run request.
+///
+/// <https://docs.github.com/en/rest/checks/runs#create-a-check-run>
+#[derive(Serialize, Debug)]
+pub struct TicketCheck {
+    pub name: &'static str,
+    pub head_sha: String,
+    pub status: &'static str,
+    pub conclusion: CheckConclusion,
+    pub output: CheckOutput,
+}
+
+/// Get the ticket check result of the pull request head commit.
+pub fn get_ticket_check(pull_request: &PullRequest, ticket_parser: &TicketParser) -> TicketCheck {
+    let (conclusion, output) = match pull_request.get_ticket_number(ticket_parser) {
+        Ok(ticket_numbers) => {
+            let mut ticket_numbers: Vec<String> = ticket_numbers.into_iter().collect();
+            ticket_numbers.sort();
+            (
+                CheckConclusion::Success,
+                CheckOutput {
+                    title: format!("Ticket number: {}", ticket_numbers.join(", ")),
+                    summary: String::from("The pull request has a ticket number."),
+                },
+            )
+        }
+        Err(e) => (
+            CheckConclusion::Failure,
+            CheckOutput {
+                title: String::from("Ticket number not found"),
+                summary: format!(
+                    "Add the ticket number to the pull request title, e.g. `[BACK-12] Fix typo`.\n\n```\n{}\n```",
+                    ticket_error_message(&e)
+                ),
+            },
+        ),
+    };
+    TicketCheck {
+        name: CHECK_NAME,
+        head_sha: pull_request.head.sha.clone(),
+        status: "completed",
+        conclusion,
+        output,
+    }
+}
+
+/// Create a completed check run in the `owner/repo_name` repository.
+/// The token needs the `checks: write` permission.
+pub async fn create_check_run(
+    octo: &Octocrab,
+    owner: &str,
+    repo_name: &str,
+    check: &TicketCheck,
+) -> octocrab::Result<serde_json::Value> {
+    octo.post(
+        format!("repos/{}/{}/check-runs", owner, repo_name),
+        Some(check),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_check_run, get_ticket_check, CheckConclusion, CHECK_NAME};
+    use crate::github_pull_request::{Head, PullRequest, TicketParser};
+    use crate::test_utils::mock_octocrab;
+    use rstest::rstest;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn pull_request(title: &str) -> PullRequest {
+        PullRequest {
+            title: title.to_string(),
+            head: Head {
+                ref_name: String::from("main"),
+                sha: String::from("abc"),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "[BACK-2][BACK-1] Title",
+        CheckConclusion::Success,
+        "Ticket number: BACK-1, BACK-2"
+    )]
+    #[case("No ticket", CheckConclusion::Failure, "Ticket number not found")]
+    fn test_get_ticket_check(
+        #[case] title: &str,
+        #[case] conclusion: CheckConclusion,
+        #[case] output_title: &str,
+    ) {
+        let check = get_ticket_check(&pull_request(title), &TicketParser::default());
+
+        assert_eq!(check.head_sha, "abc");
+        assert_eq!(check.conclusion, conclusion);
+        assert_eq!(check.output.title, output_title);
+    }
+
+    #[test]
+    fn test_get_ticket_check_failure_summary() {
+        let check = get_ticket_check(&pull_request("No ticket"), &TicketParser::default());
+
+        assert!(check.output.summary.contains("1 | No ticket"));
+        assert!(check
+            .output
+            .summary
+            .contains("expected ticket number in square brackets, e.g. [BACK-12]"));
+    }
+
+    #[tokio::test]
+    async fn test_create_check_run() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/org/app/check-runs"))
+            .and(body_partial_json(json!({
+                "name": CHECK_NAME,
+                "head_sha": "abc",
+                "status": "completed",
+                "conclusion": "failure",
+                "output": {"title": "Ticket number not found"},
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 1})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let octo = mock_octocrab(&server);
+        let check = get_ticket_check(&pull_request("No ticket"), &TicketParser::default());
+
+        create_check_run(&octo, "org", "app", &check).await.unwrap();
+    }
+}
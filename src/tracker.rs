@@ -0,0 +1,455 @@
+//! Issue trackers the ticket numbers link to, configured per project key, e.g. `BACK` tickets in
+//! one Jira, `MD` tickets in another one and `ENG` tickets in Linear.
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+
+use crate::cli::JiraLink;
+use crate::github_pull_request::{project_key, Repository, ISSUE_PROJECT_KEY};
+use crate::jira::{JiraClient, JiraCredentials, JiraTracker};
+
+/// Details of an issue shown next to its ticket number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackerIssue {
+    pub summary: String,
+    pub status: String,
+    pub issue_type: String,
+    /// Display name of the assignee, `None` for unassigned issues.
+    pub assignee: Option<String>,
+    /// The issue is resolved, e.g. its Jira status is `Done` or `Won't Do`.
+    pub closed: bool,
+}
+
+/// Tracker of the tickets of some project keys.
+#[async_trait]
+pub trait Tracker: fmt::Debug + Send + Sync {
+    /// Link to the `ticket`, e.g. `BACK-12` or `#12`.
+    fn ticket_url(&self, ticket: &str) -> String;
+
+    /// Download the issues of the `tickets`, `None` for tickets the tracker doesn't know.
+    /// Tickets that couldn't be checked are left out, trackers without the lookup check none.
+    async fn get_issues(&self, _tickets: &[String]) -> HashMap<String, Option<TrackerIssue>> {
+        HashMap::new()
+    }
+
+    /// Client of the Jira API, for the features only Jira has, e.g. the workflow transitions.
+    fn jira_client(&self) -> Option<&JiraClient> {
+        None
+    }
+}
+
+/// Linear workspace, e.g. `https://linear.app/acme/issue/ENG-12`.
+#[derive(Debug)]
+pub struct LinearTracker {
+    workspace: String,
+}
+
+impl Tracker for LinearTracker {
+    fn ticket_url(&self, ticket: &str) -> String {
+        format!("https://linear.app/{}/issue/{}", self.workspace, ticket)
+    }
+}
+
+/// YouTrack instance, e.g. `https://acme.youtrack.cloud/issue/OPS-12`.
+#[derive(Debug)]
+pub struct YouTrackTracker {
+    host: Url,
+}
+
+impl Tracker for YouTrackTracker {
+    fn ticket_url(&self, ticket: &str) -> String {
+        self.host
+            .join("issue/")
+            .unwrap()
+            .join(ticket)
+            .unwrap()
+            .to_string()
+    }
+}
+
+/// Issues of a GitHub repository, the ticket numbers are issue numbers like `#12`.
+#[derive(Debug)]
+pub struct GitHubIssuesTracker {
+    /// `owner/name` of the repository.
+    repo_full_name: String,
+}
+
+impl Tracker for GitHubIssuesTracker {
+    fn ticket_url(&self, ticket: &str) -> String {
+        format!(
+            "https://github.com/{}/issues/{}",
+            self.repo_full_name,
+            ticket.trim_start_matches(ISSUE_PROJECT_KEY)
+        )
+    }
+}
+
+/// Kind of the tracker configured for a project key, with its location.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackerConfig {
+    Jira(JiraLink),
+    /// Name of the workspace in the Linear URLs.
+    Linear(String),
+    YouTrack(Url),
+    /// `owner/name` of the repository, `None` for the repository the action runs in.
+    GitHubIssues(Option<String>),
+}
+
+/// Tracker of the tickets of a project key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerMapping {
+    /// Upper case project key, `#` for the GitHub issue numbers.
+    pub project_key: String,
+    pub tracker: TrackerConfig,
+}
+
+impl TrackerMapping {
+    /// Parse mappings separated by commas or new lines.
+    pub fn parse_list(input: &str) -> Result<Vec<Self>, String> {
+        input
+            .split([',', '\n'])
+            .filter(|v| !v.trim().is_empty())
+            .map(Self::from_str)
+            .collect()
+    }
+}
+
+/// Parse the base URL of a tracker or of Jira. Only http(s) URLs are accepted, and they get a trailing `/`,
+/// so joining the issue path to them keeps their own path, e.g. `https://acme.com/youtrack/`.
+pub fn parse_base_url(input: &str) -> Option<Url> {
+    let mut url = Url::parse(input).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Some(url)
+}
+
+impl FromStr for TrackerMapping {
+    type Err = String;
+
+    /// Parse `KEY=jira:URL`, `KEY=linear:WORKSPACE`, `KEY=youtrack:URL`, `#=github` or
+    /// `#=github:owner/name`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || {
+            format!(
+                "Invalid tracker: {}, expected KEY=jira:URL, KEY=linear:WORKSPACE, \
+KEY=youtrack:URL or #=github[:owner/name]",
+                s
+            )
+        };
+        let (project_key, tracker) = s.split_once('=').ok_or_else(invalid)?;
+        let project_key = project_key.trim().to_uppercase();
+        let (kind, target) = match tracker.split_once(':') {
+            Some((kind, target)) => (kind.trim(), Some(target.trim())),
+            None => (tracker.trim(), None),
+        };
+        let tracker = match (kind, target) {
+            ("jira", Some(host)) => {
+                TrackerConfig::Jira(JiraLink::parse(host).map_err(|_| invalid())?)
+            }
+            ("linear", Some(workspace)) if !workspace.is_empty() => {
+                TrackerConfig::Linear(workspace.to_string())
+            }
+            ("youtrack", Some(host)) => {
+                TrackerConfig::YouTrack(parse_base_url(host).ok_or_else(invalid)?)
+            }
+            ("github", None) => TrackerConfig::GitHubIssues(None),
+            ("github", Some(full_name)) if Repository::from_full_name(full_name).is_ok() => {
+                TrackerConfig::GitHubIssues(Some(full_name.to_string()))
+            }
+            _ => return Err(invalid()),
+        };
+        // GitHub issue numbers have no project key of their own, they are the only `#` tickets.
+        let is_github = matches!(tracker, TrackerConfig::GitHubIssues(_));
+        if project_key.is_empty() || (project_key == ISSUE_PROJECT_KEY) != is_github {
+            return Err(invalid());
+        }
+        Ok(Self {
+            project_key,
+            tracker,
+        })
+    }
+}
+
+/// Trackers of the ticket numbers by their project keys.
+#[derive(Debug)]
+pub struct Trackers {
+    trackers: HashMap<String, Box<dyn Tracker>>,
+    /// Tracker of the project keys without their own one, the Jira of `--jira-host`.
+    default: Option<Box<dyn Tracker>>,
+}
+
+impl Trackers {
+    /// Create the trackers of the `mappings`, falling back to `default_jira`. The Jira trackers
+    /// look the issues up if the `jira_credentials` are set. GitHub issues without the repository
+    /// belong to `repo_full_name`.
+    pub fn new(
+        mappings: &[TrackerMapping],
+        default_jira: Option<&JiraLink>,
+        jira_credentials: Option<&JiraCredentials>,
+        repo_full_name: &str,
+    ) -> Self {
+        let trackers = mappings
+            .iter()
+            .map(|mapping| {
+                let tracker: Box<dyn Tracker> = match &mapping.tracker {
+                    TrackerConfig::Jira(jira_link) => {
+                        Box::new(JiraTracker::new(jira_link.clone(), jira_credentials))
+                    }
+                    TrackerConfig::Linear(workspace) => Box::new(LinearTracker {
+                        workspace: workspace.clone(),
+                    }),
+                    TrackerConfig::YouTrack(host) => {
+                        Box::new(YouTrackTracker { host: host.clone() })
+                    }
+                    TrackerConfig::GitHubIssues(full_name) => Box::new(GitHubIssuesTracker {
+                        repo_full_name: full_name.as_deref().unwrap_or(repo_full_name).to_string(),
+                    }),
+                };
+                (mapping.project_key.clone(), tracker)
+            })
+            .collect();
+        let default = default_jira.map(|jira_link| -> Box<dyn Tracker> {
+            Box::new(JiraTracker::new(jira_link.clone(), jira_credentials))
+        });
+        Self { trackers, default }
+    }
+
+    /// Tracker of the `ticket`. GitHub issue numbers are never looked up in the default Jira.
+    fn get(&self, ticket: &str) -> Option<&dyn Tracker> {
+        let key = project_key(ticket);
+        match self.trackers.get(&key.to_uppercase()) {
+            Some(tracker) => Some(tracker.as_ref()),
+            None if key == ISSUE_PROJECT_KEY => None,
+            None => self.default.as_deref(),
+        }
+    }
+
+    /// Link to the `ticket`, `None` if no tracker is configured for it.
+    pub fn ticket_url(&self, ticket: &str) -> Option<String> {
+        self.get(ticket).map(|tracker| tracker.ticket_url(ticket))
+    }
+
+    /// Download the issues of the `tickets` from their trackers, see `Tracker::get_issues`.
+    pub async fn get_issues(&self, tickets: &[String]) -> HashMap<String, Option<TrackerIssue>> {
+        let mut issues = HashMap::new();
+        for (tracker, tickets) in self.group(tickets) {
+            issues.extend(tracker.get_issues(&tickets).await);
+        }
+        issues
+    }
+
+    /// The `tickets` tracked in Jira with the credentials set, grouped by their Jira.
+    pub fn group_by_jira_client(&self, tickets: &[String]) -> Vec<(&JiraClient, Vec<String>)> {
+        self.group(tickets)
+            .into_iter()
+            .filter_map(|(tracker, tickets)| Some((tracker.jira_client()?, tickets)))
+            .collect()
+    }
+
+    /// The `tickets` grouped by their trackers, in the order of the `tickets`. Tickets without a
+    /// tracker are left out.
+    fn group(&self, tickets: &[String]) -> Vec<(&dyn Tracker, Vec<String>)> {
+        let mut groups: Vec<(&dyn Tracker, Vec<String>)> = vec![];
+        for ticket in tickets {
+            let Some(tracker) = self.get(ticket) else {
+                continue;
+            };
+            match groups
+                .iter_mut()
+                .find(|(t, _)| std::ptr::addr_eq(*t, tracker))
+            {
+                Some((_, tickets)) => tickets.push(ticket.clone()),
+                None => groups.push((tracker, vec![ticket.clone()])),
+            }
+        }
+        groups
+    }
+}
+
+/// What is known about the ticket numbers: the trackers they link to and the issues downloaded
+/// from them.
+#[derive(Debug, Default)]
+pub struct TicketDetails<'a> {
+    pub trackers: Option<&'a Trackers>,
+    /// `None` for tickets the tracker doesn't know. Tickets that weren't checked are missing.
+    pub issues: HashMap<String, Option<TrackerIssue>>,
+}
+
+impl TicketDetails<'_> {
+    /// Link to the `ticket`, if a tracker is configured for it.
+    pub fn ticket_url(&self, ticket: &str) -> Option<String> {
+        self.trackers?.ticket_url(ticket)
+    }
+
+    /// What's wrong with the `ticket`, if its tracker knows it's wrong. Tickets that couldn't be
+    /// checked are fine.
+    pub fn ticket_problem(&self, ticket: &str) -> Option<&'static str> {
+        match self.issues.get(ticket)? {
+            None => Some("not found"),
+            Some(issue) if issue.closed => Some("closed"),
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrackerConfig, TrackerMapping, Trackers};
+    use crate::cli::JiraLink;
+    use crate::jira::JiraCredentials;
+    use crate::secret::Secret;
+    use rstest::rstest;
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[rstest]
+    #[case("BACK=jira:https://back.atlassian.net", Ok(("BACK", TrackerConfig::Jira(JiraLink::parse("https://back.atlassian.net").unwrap()))))]
+    #[case("BACK=jira:https://acme.com/jira", Ok(("BACK", TrackerConfig::Jira(JiraLink::parse("https://acme.com/jira/").unwrap()))))]
+    #[case(" eng = linear:acme ", Ok(("ENG", TrackerConfig::Linear(String::from("acme")))))]
+    #[case("OPS=youtrack:https://acme.youtrack.cloud", Ok(("OPS", TrackerConfig::YouTrack(Url::parse("https://acme.youtrack.cloud").unwrap()))))]
+    #[case("OPS=youtrack:https://acme.com/youtrack", Ok(("OPS", TrackerConfig::YouTrack(Url::parse("https://acme.com/youtrack/").unwrap()))))]
+    #[case("OPS=youtrack:https://acme.com/youtrack/", Ok(("OPS", TrackerConfig::YouTrack(Url::parse("https://acme.com/youtrack/").unwrap()))))]
+    #[case("#=github", Ok(("#", TrackerConfig::GitHubIssues(None))))]
+    #[case("#=github:org/docs", Ok(("#", TrackerConfig::GitHubIssues(Some(String::from("org/docs"))))))]
+    #[case("BACK=github", Err(()))]
+    #[case("#=jira:https://back.atlassian.net", Err(()))]
+    #[case("BACK=jira", Err(()))]
+    #[case("BACK=jira:not a url", Err(()))]
+    #[case("BACK=jira:ftp://back.example.com", Err(()))]
+    #[case("OPS=youtrack:mailto:x", Err(()))]
+    #[case("OPS=youtrack:ftp://acme.com/youtrack", Err(()))]
+    #[case("ENG=linear:", Err(()))]
+    #[case("#=github:docs", Err(()))]
+    #[case("=linear:acme", Err(()))]
+    #[case("BACK=redmine:https://redmine.example.com", Err(()))]
+    #[case("BACK", Err(()))]
+    fn test_parse_tracker_mapping(
+        #[case] input: &str,
+        #[case] expected: Result<(&str, TrackerConfig), ()>,
+    ) {
+        let result = input.parse::<TrackerMapping>();
+        match expected {
+            Ok((project_key, tracker)) => assert_eq!(
+                result.unwrap(),
+                TrackerMapping {
+                    project_key: project_key.to_string(),
+                    tracker,
+                }
+            ),
+            Err(_) => assert!(result.unwrap_err().starts_with("Invalid tracker")),
+        }
+    }
+
+    #[rstest]
+    #[case("BACK-1", Some("https://back.atlassian.net/browse/BACK-1"))]
+    #[case("back-1", Some("https://back.atlassian.net/browse/back-1"))]
+    #[case("MD-2", Some("https://md.atlassian.net/browse/MD-2"))]
+    #[case("ENG-3", Some("https://linear.app/acme/issue/ENG-3"))]
+    #[case("OPS-4", Some("https://acme.youtrack.cloud/issue/OPS-4"))]
+    #[case("INFRA-7", Some("https://acme.com/youtrack/issue/INFRA-7"))]
+    #[case("#5", Some("https://github.com/org/app/issues/5"))]
+    #[case("CORE-6", Some("https://default.atlassian.net/browse/CORE-6"))]
+    fn test_trackers_ticket_url(#[case] ticket: &str, #[case] expected: Option<&str>) {
+        let mappings = TrackerMapping::parse_list(
+            "BACK=jira:https://back.atlassian.net, MD=jira:https://md.atlassian.net\n\
+ENG=linear:acme\nOPS=youtrack:https://acme.youtrack.cloud\n#=github\n\
+INFRA=youtrack:https://acme.com/youtrack",
+        )
+        .unwrap();
+        let default_jira = JiraLink::parse("https://default.atlassian.net").unwrap();
+        let trackers = Trackers::new(&mappings, Some(&default_jira), None, "org/app");
+
+        assert_eq!(trackers.ticket_url(ticket).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("CORE-6")]
+    #[case("#5")]
+    fn test_trackers_ticket_url_without_tracker(#[case] ticket: &str) {
+        let mappings = TrackerMapping::parse_list("ENG=linear:acme").unwrap();
+        let trackers = Trackers::new(&mappings, None, None, "org/app");
+
+        assert_eq!(trackers.ticket_url(ticket), None);
+    }
+
+    async fn mock_jira(summary: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/issue/BACK-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "fields": {
+                    "summary": summary,
+                    "status": {"name": "In Review", "statusCategory": {"key": "indeterminate"}},
+                    "issuetype": {"name": "Bug"},
+                    "assignee": null,
+                }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/issue/MD-1"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_trackers_get_issues() {
+        // Both servers know BACK-1, each ticket has to be looked up in the Jira of its project.
+        let back_jira = mock_jira("Back issue").await;
+        let default_jira = mock_jira("Default issue").await;
+        let mappings = vec![
+            TrackerMapping {
+                project_key: String::from("BACK"),
+                tracker: TrackerConfig::Jira(JiraLink::parse(&back_jira.uri()).unwrap()),
+            },
+            "ENG=linear:acme".parse().unwrap(),
+        ];
+        let credentials = JiraCredentials {
+            email: String::from("bot@example.com"),
+            api_token: Secret::new(String::from("token")),
+        };
+        let trackers = Trackers::new(
+            &mappings,
+            Some(&JiraLink::parse(&default_jira.uri()).unwrap()),
+            Some(&credentials),
+            "org/app",
+        );
+
+        let issues = trackers
+            .get_issues(&["BACK-1", "ENG-1", "MD-1"].map(String::from))
+            .await;
+
+        let mut summaries: Vec<(&str, Option<&str>)> = issues
+            .iter()
+            .map(|(ticket, issue)| (ticket.as_str(), issue.as_ref().map(|i| i.summary.as_str())))
+            .collect();
+        summaries.sort();
+        assert_eq!(
+            summaries,
+            vec![("BACK-1", Some("Back issue")), ("MD-1", None)]
+        );
+        let jira_groups: Vec<Vec<String>> = trackers
+            .group_by_jira_client(&["BACK-1", "ENG-1", "MD-1", "BACK-2"].map(String::from))
+            .into_iter()
+            .map(|(_, tickets)| tickets)
+            .collect();
+        assert_eq!(
+            jira_groups,
+            vec![
+                vec![String::from("BACK-1"), String::from("BACK-2")],
+                vec![String::from("MD-1")]
+            ]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+/// Write the test pull request event with the `action` to a temporary file, returns its path.
+/// The test payload is an `edited` event, which the action skips.
+pub fn pull_request_event_path(action: &str, test_name: &str) -> PathBuf {
+    let payload = std::fs::read_to_string("src/test_resources/action_payload.json").unwrap();
+    let mut payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+    payload["action"] = serde_json::Value::from(action);
+    let path = std::env::temp_dir().join(format!(
+        "km-action-{}-{}.json",
+        test_name,
+        std::process::id()
+    ));
+    std::fs::write(&path, payload.to_string()).unwrap();
+    path
+}
@@ -0,0 +1,46 @@
+//! Run the action binary and check failures are reported with their exit codes, without panics.
+mod common;
+
+use rstest::rstest;
+use std::process::Command;
+
+/// Replaced with the path of an `opened` pull request event.
+const OPENED_EVENT: &str = "<opened event>";
+
+#[rstest]
+#[case(&[], 2, "::error::Invalid configuration: Missing required input: pass --app-id")]
+#[case(&[("GITHUB_EVENT_PATH", "src/test_resources/action_payload.json")], 2, "::error::Invalid configuration: Env GITHUB_TOKEN not found.")]
+#[case(&[("GITHUB_TOKEN", "token"), ("GITHUB_EVENT_PATH", "missing.json")], 3, "::error::Couldn't read missing.json: ")]
+#[case(&[("GITHUB_TOKEN", "token"), ("GITHUB_EVENT_PATH", "Cargo.toml")], 4, "::error::Invalid event payload: ")]
+#[case(&[("GITHUB_TOKEN", "token"), ("GITHUB_EVENT_PATH", OPENED_EVENT)], 5, "::error::Couldn't authenticate for the lib repositories of umatbro: invalid app private key")]
+#[case(&[("GITHUB_TOKEN", "token"), ("GITHUB_EVENT_PATH", "src/test_resources/action_payload.json")], 0, "Skipping the event. Neither the title nor the base")]
+#[case(&[("GITHUB_TOKEN", "token"), ("GITHUB_EVENT_NAME", "release"), ("GITHUB_EVENT_PATH", "src/test_resources/action_payload.json")], 0, "::warning::The release event is not supported")]
+fn test_exit_codes(#[case] env: &[(&str, &str)], #[case] exit_code: i32, #[case] message: &str) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_be-keto-mojo-gh-bot-action"));
+    command.env_clear();
+    if !env.is_empty() {
+        command
+            .env("INPUT_APP_ID", "12")
+            .env("INPUT_APP_PRIVATE_KEY", "not a key");
+    }
+    for (key, value) in env {
+        match (*key, *value) {
+            ("GITHUB_EVENT_PATH", OPENED_EVENT) => command.env(
+                key,
+                common::pull_request_event_path("opened", &format!("exit-code-{}", exit_code)),
+            ),
+            _ => command.env(key, value),
+        };
+    }
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(exit_code), "{}", stdout);
+    assert!(
+        stdout.lines().any(|line| line.starts_with(message)),
+        "{}",
+        stdout
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}